@@ -1,35 +1,61 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use rand_xoshiro::rand_core::SeedableRng;
-use raytracing::consts::*;
-use raytracing::renderer::render;
+use raytracing::geometry::bvh::BvhBuilder;
+use raytracing::renderer::{render, RenderSettings};
 use raytracing::scene::Scene;
 
 fn bench_three_spheres(c: &mut Criterion) {
     let scene = Scene::bench_three_spheres();
+    let settings = RenderSettings::default();
 
     c.bench_function("render three_spheres", |b| {
         b.iter(|| {
-            render(black_box(&scene), IMAGE_WIDTH, IMAGE_HEIGHT);
+            render(black_box(&scene), &settings);
         })
     });
 }
 
 fn bench_big_scene(c: &mut Criterion) {
     let scene = Scene::big_scene();
+    let settings = RenderSettings::default();
 
     c.bench_function("render big_scene", |b| {
         b.iter(|| {
-            render(black_box(&scene), IMAGE_WIDTH, IMAGE_HEIGHT);
+            render(black_box(&scene), &settings);
         })
     });
 }
 
+fn bench_bvh_builders(c: &mut Criterion) {
+    let settings = RenderSettings::default();
+    let builders = [
+        ("median", BvhBuilder::RandomMedian),
+        ("sah", BvhBuilder::Sah { max_leaf_size: 4 }),
+    ];
+
+    for (name, builder) in builders {
+        let mut scene = Scene::big_scene();
+        scene.build_bvh(builder);
+        println!(
+            "big_scene {name} BVH: {}",
+            scene.hittable_list().bvh_stats()
+        );
+
+        c.bench_function(&format!("render big_scene {name} bvh"), |b| {
+            b.iter(|| {
+                render(black_box(&scene), &settings);
+            })
+        });
+    }
+}
+
 fn bench_cornell_box(c: &mut Criterion) {
     let scene = Scene::cornell_box();
+    let settings = RenderSettings::default();
 
     c.bench_function("render cornell_box", |b| {
         b.iter(|| {
-            render(black_box(&scene), IMAGE_WIDTH, IMAGE_HEIGHT);
+            render(black_box(&scene), &settings);
         })
     });
 }
@@ -37,10 +63,11 @@ fn bench_cornell_box(c: &mut Criterion) {
 fn bench_perlin_and_earth(c: &mut Criterion) {
     let mut rng = rand_xoshiro::Xoshiro256Plus::seed_from_u64(0);
     let scene = Scene::perlin_and_earth(&mut rng);
+    let settings = RenderSettings::default();
 
     c.bench_function("render perlin_and_earth", |b| {
         b.iter(|| {
-            render(black_box(&scene), IMAGE_WIDTH, IMAGE_HEIGHT);
+            render(black_box(&scene), &settings);
         })
     });
 }
@@ -48,6 +75,6 @@ fn bench_perlin_and_earth(c: &mut Criterion) {
 criterion_group! {
     name = benches;
     config = Criterion::default().sample_size(10);
-    targets = bench_three_spheres, bench_big_scene, bench_cornell_box, bench_perlin_and_earth
+    targets = bench_three_spheres, bench_big_scene, bench_bvh_builders, bench_cornell_box, bench_perlin_and_earth
 }
 criterion_main!(benches);
@@ -0,0 +1,158 @@
+use crate::math::color::Color;
+use glam::Vec3A;
+
+/// Object id of the pixels whose first sample hit nothing.
+pub const NO_OBJECT: u32 = u32::MAX;
+
+/// Arbitrary output variable, one of the attributes of the first hit of the
+/// camera rays rendered next to the image.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Aov {
+    Albedo,
+    Normal,
+    Depth,
+    ObjectId,
+    Emission,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 5] = [
+        Aov::Albedo,
+        Aov::Normal,
+        Aov::Depth,
+        Aov::ObjectId,
+        Aov::Emission,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aov::Albedo => "albedo",
+            Aov::Normal => "normal",
+            Aov::Depth => "depth",
+            Aov::ObjectId => "object_id",
+            Aov::Emission => "emission",
+        }
+    }
+}
+
+/// Attributes of the first hit of the camera rays of a pixel, averaged over
+/// its samples.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AovPixel {
+    pub albedo: Color,
+    /// World space normal, on the side of the camera, zero where nothing
+    /// was hit.
+    pub normal: Vec3A,
+    /// Distance from the camera to the hit, infinite where nothing was hit.
+    pub depth: f32,
+    /// [`HittableWorld::object_id`](crate::geometry::hittable_world::HittableWorld::object_id)
+    /// of the object hit by the first sample, or [`NO_OBJECT`].
+    pub object_id: u32,
+    pub emission: Color,
+}
+
+impl Default for AovPixel {
+    fn default() -> Self {
+        Self {
+            albedo: Color::black(),
+            normal: Vec3A::ZERO,
+            depth: f32::INFINITY,
+            object_id: NO_OBJECT,
+            emission: Color::black(),
+        }
+    }
+}
+
+/// Output variables of each pixel, row by row from the top left corner.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Aovs {
+    width: usize,
+    height: usize,
+    pixels: Vec<AovPixel>,
+}
+
+impl Aovs {
+    /// # Panics
+    ///
+    /// If there is not exactly one pixel per position of the image.
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<AovPixel>) -> Self {
+        assert_eq!(
+            pixels.len(),
+            width * height,
+            "The output variables need one pixel per position"
+        );
+
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[AovPixel] {
+        &self.pixels
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> AovPixel {
+        self.pixels[y * self.width + x]
+    }
+
+    /// Converts one of the variables to colors that can be viewed.
+    ///
+    /// Normals are moved from [-1, 1] to [0, 1], depths are grey levels
+    /// decreasing from white at the nearest hit to dark grey at the farthest,
+    /// black where nothing was hit, and each object gets a random color.
+    pub fn visualize(&self, aov: Aov) -> Vec<Color> {
+        let hits = self
+            .pixels
+            .iter()
+            .map(|pixel| pixel.depth)
+            .filter(|depth| depth.is_finite());
+        let nearest = hits.clone().fold(f32::INFINITY, f32::min);
+        let farthest = hits.fold(nearest, f32::max);
+
+        self.pixels
+            .iter()
+            .map(|pixel| match aov {
+                Aov::Albedo => pixel.albedo,
+                Aov::Normal => {
+                    let normal = 0.5 * pixel.normal + 0.5;
+                    Color::new(normal.x, normal.y, normal.z)
+                }
+                Aov::Depth => {
+                    let grey = if !pixel.depth.is_finite() {
+                        0.0
+                    } else if farthest > nearest {
+                        1.0 - 0.9 * (pixel.depth - nearest) / (farthest - nearest)
+                    } else {
+                        1.0
+                    };
+                    Color::new(grey, grey, grey)
+                }
+                Aov::ObjectId => object_color(pixel.object_id),
+                Aov::Emission => pixel.emission,
+            })
+            .collect()
+    }
+}
+
+fn object_color(object_id: u32) -> Color {
+    if object_id == NO_OBJECT {
+        return Color::black();
+    }
+
+    // Spreads consecutive ids to unrelated colors.
+    let mut hash = (object_id as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    hash ^= hash >> 31;
+    let [r, g, b, ..] = hash.to_le_bytes();
+
+    Color::from_rgb(r, g, b)
+}
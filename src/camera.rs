@@ -1,12 +1,19 @@
 use crate::consts::ASPECT_RATIO;
 use crate::math::vec3::Vec3Ext;
 use crate::ray::Ray;
+use crate::sampler::{Sampler, LENS_DIMENSIONS, TIME_DIMENSIONS};
 use glam::Vec3A;
 use rand::Rng;
-use rand_xoshiro::rand_core::RngCore;
 use tracy_full::zone;
 
 pub struct Camera {
+    look_from: Vec3A,
+    look_at: Vec3A,
+    vup: Vec3A,
+    vertical_fov: f32,
+    aspect_ratio: f32,
+    aperture: f32,
+    focus_distance: f32,
     origin: Vec3A,
     lower_left_corner: Vec3A,
     horizontal: Vec3A,
@@ -41,6 +48,13 @@ impl Camera {
         let horizontal = focus_distance * viewport_width * u;
         let vertical = focus_distance * viewport_height * v;
         Self {
+            look_from,
+            look_at,
+            vup,
+            vertical_fov,
+            aspect_ratio,
+            aperture,
+            focus_distance,
             origin,
             horizontal,
             vertical,
@@ -67,9 +81,26 @@ impl Camera {
         cam
     }
 
-    pub fn get_ray(&self, s: f32, t: f32, rng: &mut impl RngCore) -> Ray {
+    /// Creates the same camera for an image with a different aspect ratio.
+    pub fn with_aspect_ratio(&self, aspect_ratio: f32) -> Self {
+        let mut cam = Self::new(
+            self.look_from,
+            self.look_at,
+            self.vup,
+            self.vertical_fov,
+            aspect_ratio,
+            self.aperture,
+            self.focus_distance,
+        );
+        cam.set_time(self.time0, self.time1);
+
+        cam
+    }
+
+    pub fn get_ray(&self, s: f32, t: f32, sampler: &mut impl Sampler) -> Ray {
         zone!();
-        let rd = self.lens_radius * Vec3A::random_in_unit_circle(rng);
+        sampler.start_dimensions(LENS_DIMENSIONS);
+        let rd = self.lens_radius * Vec3A::random_in_unit_circle(sampler);
         let offset = self.u * rd.x + self.v * rd.y;
 
         let mut ray = Ray::new(
@@ -77,7 +108,8 @@ impl Camera {
             self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset,
         );
 
-        ray.time = rng.gen_range(self.time0..self.time1);
+        sampler.start_dimensions(TIME_DIMENSIONS);
+        ray.time = sampler.gen_range(self.time0..self.time1);
 
         ray
     }
@@ -86,6 +118,42 @@ impl Camera {
         self.time0 = time0;
         self.time1 = time1;
     }
+
+    pub fn look_from(&self) -> Vec3A {
+        self.look_from
+    }
+
+    pub fn look_at(&self) -> Vec3A {
+        self.look_at
+    }
+
+    pub fn vup(&self) -> Vec3A {
+        self.vup
+    }
+
+    pub fn vertical_fov(&self) -> f32 {
+        self.vertical_fov
+    }
+
+    pub fn aspect_ratio(&self) -> f32 {
+        self.aspect_ratio
+    }
+
+    pub fn aperture(&self) -> f32 {
+        self.aperture
+    }
+
+    pub fn focus_distance(&self) -> f32 {
+        self.focus_distance
+    }
+
+    pub fn time0(&self) -> f32 {
+        self.time0
+    }
+
+    pub fn time1(&self) -> f32 {
+        self.time1
+    }
 }
 
 impl Default for Camera {
@@ -0,0 +1,200 @@
+use crate::math::color::Color;
+use crate::scene_description::SceneDescriptionError;
+use glam::Vec3A;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// First bytes of a checkpoint, changed with its layout.
+const MAGIC: &[u8; 8] = b"RTCKPT01";
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(std::io::Error),
+    /// The file is not a checkpoint, or one of another version.
+    NotACheckpoint,
+    /// The checkpoint ends before all the samples, which were not completely
+    /// written.
+    Truncated,
+    /// The checkpoint was rendered with a scene or settings other than the
+    /// ones of the render resuming it.
+    OtherSettings,
+    /// The scene can not be described, so a checkpoint of it could be resumed
+    /// with another scene.
+    UndescribableScene(SceneDescriptionError),
+}
+
+impl Display for CheckpointError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckpointError::Io(err) => write!(f, "{err}"),
+            CheckpointError::NotACheckpoint => write!(f, "The file is not a render checkpoint"),
+            CheckpointError::Truncated => write!(f, "The checkpoint is truncated"),
+            CheckpointError::OtherSettings => write!(
+                f,
+                "The checkpoint was rendered with another scene or other settings, only the samples per pixel can change"
+            ),
+            CheckpointError::UndescribableScene(err) => {
+                write!(f, "The scene can not be checkpointed : {err}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {}
+
+impl From<std::io::Error> for CheckpointError {
+    fn from(err: std::io::Error) -> Self {
+        if err.kind() == std::io::ErrorKind::UnexpectedEof {
+            Self::Truncated
+        } else {
+            Self::Io(err)
+        }
+    }
+}
+
+/// Hashes `bytes` with the 64 bits FNV-1a hash, which unlike the hasher of
+/// the standard library does not change between versions.
+pub(crate) fn fingerprint(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Writes the state of a render as little endian numbers, to a checkpoint
+/// file or to a distributed render.
+pub(crate) struct CheckpointWriter<W: Write = BufWriter<File>> {
+    writer: W,
+}
+
+impl CheckpointWriter {
+    pub(crate) fn create(path: &Path) -> Result<Self, CheckpointError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+
+        Ok(Self { writer })
+    }
+
+    /// Flushes the checkpoint and waits for it to reach the disk.
+    pub(crate) fn finish(self) -> Result<(), CheckpointError> {
+        let file = self.writer.into_inner().map_err(|err| err.into_error())?;
+        Ok(file.sync_all()?)
+    }
+}
+
+impl<W: Write> CheckpointWriter<W> {
+    /// Writes the numbers without the header of the checkpoint files.
+    pub(crate) fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub(crate) fn flush(&mut self) -> Result<(), CheckpointError> {
+        Ok(self.writer.flush()?)
+    }
+
+    pub(crate) fn into_inner(self) -> W {
+        self.writer
+    }
+
+    pub(crate) fn u32(&mut self, value: u32) -> Result<(), CheckpointError> {
+        Ok(self.writer.write_all(&value.to_le_bytes())?)
+    }
+
+    pub(crate) fn f32(&mut self, value: f32) -> Result<(), CheckpointError> {
+        Ok(self.writer.write_all(&value.to_le_bytes())?)
+    }
+
+    pub(crate) fn color(&mut self, color: Color) -> Result<(), CheckpointError> {
+        self.f32(color.x)?;
+        self.f32(color.y)?;
+        self.f32(color.z)
+    }
+
+    pub(crate) fn vec3(&mut self, vector: Vec3A) -> Result<(), CheckpointError> {
+        self.f32(vector.x)?;
+        self.f32(vector.y)?;
+        self.f32(vector.z)
+    }
+
+    pub(crate) fn bytes(&mut self, bytes: &[u8]) -> Result<(), CheckpointError> {
+        self.u32(bytes.len() as u32)?;
+        Ok(self.writer.write_all(bytes)?)
+    }
+}
+
+/// Reads the numbers written by a [`CheckpointWriter`].
+pub(crate) struct CheckpointReader<R: Read = BufReader<File>> {
+    reader: R,
+}
+
+impl CheckpointReader {
+    pub(crate) fn open(path: &Path) -> Result<Self, CheckpointError> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0; MAGIC.len()];
+        match reader.read_exact(&mut magic) {
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => {
+                return Err(CheckpointError::NotACheckpoint);
+            }
+            result => result?,
+        }
+        if &magic != MAGIC {
+            return Err(CheckpointError::NotACheckpoint);
+        }
+
+        Ok(Self { reader })
+    }
+
+    /// Checks that the whole checkpoint was read.
+    pub(crate) fn finish(mut self) -> Result<(), CheckpointError> {
+        let mut byte = [0];
+        match self.reader.read(&mut byte)? {
+            0 => Ok(()),
+            _ => Err(CheckpointError::NotACheckpoint),
+        }
+    }
+}
+
+impl<R: Read> CheckpointReader<R> {
+    /// Reads numbers without the header of the checkpoint files.
+    pub(crate) fn new(reader: R) -> Self {
+        Self { reader }
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, CheckpointError> {
+        let mut bytes = [0; 4];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    pub(crate) fn f32(&mut self) -> Result<f32, CheckpointError> {
+        Ok(f32::from_bits(self.u32()?))
+    }
+
+    pub(crate) fn color(&mut self) -> Result<Color, CheckpointError> {
+        Ok(Color::new(self.f32()?, self.f32()?, self.f32()?))
+    }
+
+    pub(crate) fn vec3(&mut self) -> Result<Vec3A, CheckpointError> {
+        Ok(Vec3A::new(self.f32()?, self.f32()?, self.f32()?))
+    }
+
+    pub(crate) fn bytes(&mut self) -> Result<Vec<u8>, CheckpointError> {
+        let len = self.u32()?;
+        self.exact_bytes(len as usize)
+    }
+
+    /// Reads `len` bytes, allocated as they are read so that a wrong length
+    /// fails before allocating it.
+    pub(crate) fn exact_bytes(&mut self, len: usize) -> Result<Vec<u8>, CheckpointError> {
+        let mut bytes = Vec::new();
+        (&mut self.reader)
+            .take(len as u64)
+            .read_to_end(&mut bytes)?;
+        if bytes.len() < len {
+            return Err(CheckpointError::Truncated);
+        }
+
+        Ok(bytes)
+    }
+}
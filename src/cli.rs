@@ -0,0 +1,337 @@
+use crate::consts::{
+    ASPECT_RATIO, IMAGE_WIDTH, MAX_DEPTH, RUSSIAN_ROULETTE_DEPTH, SAMPLES_PER_PIXEL, TILE_SIZE,
+};
+use crate::denoise::DenoiseSettings;
+use crate::film::Filter;
+use crate::geometry::bvh::BvhBuilder;
+use crate::output::{ExrPrecision, OutputSettings};
+use crate::renderer::{AdaptiveSampling, RenderSettings};
+use crate::sampler::SamplerKind;
+use crate::tile::TileOrder;
+use crate::tone_mapping::ToneMapping;
+use clap::{Parser, ValueEnum};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// CPU path tracer rendering built-in scenes, TOML scene files or glTF files
+/// to PNG.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Built-in scene to render, see `--list-scenes`.
+    #[arg(
+        long,
+        default_value = "perlin_and_earth",
+        conflicts_with = "scene_file"
+    )]
+    pub scene: String,
+
+    /// TOML scene file, or `.gltf`/`.glb` file, to render instead of a
+    /// built-in scene.
+    #[arg(long, value_name = "PATH")]
+    pub scene_file: Option<PathBuf>,
+
+    /// Width of the image in pixels.
+    #[arg(long, default_value_t = IMAGE_WIDTH as u32, value_parser = clap::value_parser!(u32).range(2..))]
+    pub width: u32,
+
+    /// Height of the image in pixels, computed from the width and the
+    /// default aspect ratio when omitted.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(2..))]
+    pub height: Option<u32>,
+
+    /// Number of samples per pixel.
+    #[arg(long, default_value_t = SAMPLES_PER_PIXEL, value_parser = clap::value_parser!(u32).range(1..))]
+    pub spp: u32,
+
+    /// Samples each pixel until the standard error of its mean luminance,
+    /// relative to it, falls below this threshold, with at most `--spp`
+    /// samples.
+    #[arg(long)]
+    pub adaptive_threshold: Option<f32>,
+
+    /// Number of samples of every pixel before adaptive sampling estimates
+    /// its noise.
+    #[arg(long, default_value_t = AdaptiveSampling::default().min_samples, value_parser = clap::value_parser!(u32).range(2..))]
+    pub min_spp: u32,
+
+    /// Also writes a PNG heatmap of the number of samples of each pixel, as
+    /// `<name>.samples.png`.
+    #[arg(long)]
+    pub sample_heatmap: bool,
+
+    /// Renders in passes of this many samples per pixel, writing the image
+    /// after each pass.
+    #[arg(long, value_name = "SAMPLES", value_parser = clap::value_parser!(u32).range(1..))]
+    pub progressive: Option<u32>,
+
+    /// Periodically saves the state of the render to this file, from which
+    /// `--resume` continues it. The render is then done in passes of
+    /// `--progressive` samples, or of one sample.
+    #[arg(long, value_name = "PATH")]
+    pub checkpoint: Option<PathBuf>,
+
+    /// Seconds between two saves of the checkpoint.
+    #[arg(long, value_name = "SECONDS", default_value_t = 300)]
+    pub checkpoint_interval: u64,
+
+    /// Continues the render saved in the `--checkpoint` file up to `--spp`
+    /// samples per pixel, with the same other options.
+    #[arg(long, requires = "checkpoint")]
+    pub resume: bool,
+
+    /// Stops the render after this duration, such as `90s`, `5m` or `2h`, or
+    /// seconds without unit, and writes the image of the samples taken.
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub time_limit: Option<Duration>,
+
+    /// Renders on the worker processes connecting to this address, such as
+    /// `127.0.0.1:7878`, instead of locally.
+    #[arg(long, value_name = "ADDRESS", conflicts_with_all = ["progressive", "checkpoint", "time_limit"])]
+    pub listen: Option<String>,
+
+    /// Renders the tiles handed out by the process listening at this
+    /// address, which sends the scene and the render options.
+    #[arg(long, value_name = "ADDRESS")]
+    pub worker: Option<String>,
+
+    /// Maximum number of bounces of a path.
+    #[arg(long, default_value_t = MAX_DEPTH)]
+    pub max_depth: u32,
+
+    /// Number of bounces after which paths are randomly terminated by
+    /// Russian roulette.
+    #[arg(long, default_value_t = RUSSIAN_ROULETTE_DEPTH)]
+    pub russian_roulette_depth: u32,
+
+    /// Seed of the random generators used to build and render the scene.
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+
+    /// Exposure of PNG images, in stops.
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    pub exposure: f32,
+
+    /// Operator mapping the radiance of PNG images to their range.
+    #[arg(long, value_enum, default_value_t = ToneMappingKind::Clamp)]
+    pub tone_mapping: ToneMappingKind,
+
+    /// Luminance mapped to white by the extended Reinhard tone mapping.
+    #[arg(long, default_value_t = 4.0)]
+    pub white_point: f32,
+
+    /// Type of the channels of OpenEXR images.
+    #[arg(long, value_enum, default_value_t = ExrPrecision::Half)]
+    pub exr_precision: ExrPrecision,
+
+    /// Maximum value of each channel of a sample, to remove fireflies.
+    #[arg(long)]
+    pub clamp: Option<f32>,
+
+    /// Only finds the lights by bouncing, without sampling them at diffuse
+    /// hits.
+    #[arg(long)]
+    pub no_light_sampling: bool,
+
+    /// Also renders the albedo, normal, depth, object ID and emission of the
+    /// first hits, as layers of OpenEXR images or as `<name>.<aov>.<ext>`
+    /// files next to the other images.
+    #[arg(long)]
+    pub aovs: bool,
+
+    /// Also writes the image denoised by a joint bilateral filter guided by
+    /// the AOVs, as `<name>.denoised.<ext>`.
+    #[arg(long)]
+    pub denoise: bool,
+
+    /// Half width in pixels of the neighbourhood averaged by the denoiser.
+    #[arg(long, default_value_t = DenoiseSettings::default().radius as u32)]
+    pub denoise_radius: u32,
+
+    /// Algorithm building the BVH of the scene.
+    #[arg(long, value_enum, default_value_t = BvhKind::Median)]
+    pub bvh: BvhKind,
+
+    /// Maximum number of primitives in a leaf of a SAH BVH.
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..))]
+    pub bvh_leaf_size: u32,
+
+    /// Prints the node count, depth and SAH cost of the BVH before rendering.
+    #[arg(long)]
+    pub bvh_stats: bool,
+
+    /// Reconstruction filter weighting the samples in the pixels around them.
+    #[arg(long, value_enum, default_value_t = FilterKind::Box)]
+    pub filter: FilterKind,
+
+    /// Radius of the reconstruction filter in pixels, defaulting to 0.5 for
+    /// the box, 1 for the tent, 1.5 for the Gaussian and 2 for the others.
+    #[arg(long)]
+    pub filter_radius: Option<f32>,
+
+    /// Distribution of the random numbers of the samples of each pixel.
+    #[arg(long, value_enum, default_value_t = SamplerKind::Independent)]
+    pub sampler: SamplerKind,
+
+    /// Width and height in pixels of the tiles rendered by each thread.
+    #[arg(long, default_value_t = TILE_SIZE as u32, value_parser = clap::value_parser!(u32).range(1..))]
+    pub tile_size: u32,
+
+    /// Order in which the tiles are rendered.
+    #[arg(long, value_enum, default_value_t = TileOrder::Hilbert)]
+    pub tile_order: TileOrder,
+
+    /// Number of render threads, defaults to one per logical core.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub threads: Option<u32>,
+
+    /// Path of the image to write, whose extension selects PNG, OpenEXR
+    /// (`.exr`) or Radiance HDR (`.hdr`).
+    #[arg(short, long, default_value = "out.png")]
+    pub output: PathBuf,
+
+    /// Writes the selected scene as a TOML scene file instead of rendering it.
+    #[arg(long, value_name = "PATH")]
+    pub export_scene: Option<PathBuf>,
+
+    /// Prints the names of the built-in scenes and exits.
+    #[arg(long)]
+    pub list_scenes: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum BvhKind {
+    /// Median split along a random axis.
+    Median,
+    /// Binned surface area heuristic.
+    Sah,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ToneMappingKind {
+    /// Clamps each channel to one.
+    Clamp,
+    Reinhard,
+    /// Reinhard reaching white at `--white-point`.
+    ExtendedReinhard,
+    /// ACES filmic.
+    Aces,
+    Agx,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum FilterKind {
+    Box,
+    Tent,
+    Gaussian,
+    /// Mitchell-Netravali with B = C = 1/3.
+    Mitchell,
+    Lanczos,
+}
+
+impl Cli {
+    pub fn bvh_builder(&self) -> BvhBuilder {
+        match self.bvh {
+            BvhKind::Median => BvhBuilder::RandomMedian,
+            BvhKind::Sah => BvhBuilder::Sah {
+                max_leaf_size: self.bvh_leaf_size as usize,
+            },
+        }
+    }
+
+    pub fn filter(&self) -> Filter {
+        let radius = |default| self.filter_radius.unwrap_or(default);
+        match self.filter {
+            FilterKind::Box => Filter::Box {
+                radius: radius(0.5),
+            },
+            FilterKind::Tent => Filter::Tent {
+                radius: radius(1.0),
+            },
+            FilterKind::Gaussian => {
+                let radius = radius(1.5);
+                Filter::Gaussian {
+                    radius,
+                    sigma: radius / 3.0,
+                }
+            }
+            FilterKind::Mitchell => Filter::Mitchell {
+                radius: radius(2.0),
+                b: 1.0 / 3.0,
+                c: 1.0 / 3.0,
+            },
+            FilterKind::Lanczos => Filter::Lanczos {
+                radius: radius(2.0),
+            },
+        }
+    }
+
+    pub fn denoise_settings(&self) -> DenoiseSettings {
+        DenoiseSettings {
+            radius: self.denoise_radius as usize,
+            ..Default::default()
+        }
+    }
+
+    pub fn output_settings(&self) -> OutputSettings {
+        let tone_mapping = match self.tone_mapping {
+            ToneMappingKind::Clamp => ToneMapping::Clamp,
+            ToneMappingKind::Reinhard => ToneMapping::Reinhard,
+            ToneMappingKind::ExtendedReinhard => ToneMapping::ExtendedReinhard {
+                white: self.white_point,
+            },
+            ToneMappingKind::Aces => ToneMapping::Aces,
+            ToneMappingKind::Agx => ToneMapping::Agx,
+        };
+
+        OutputSettings {
+            exposure: self.exposure,
+            tone_mapping,
+            exr_precision: self.exr_precision,
+        }
+    }
+
+    pub fn render_settings(&self) -> RenderSettings {
+        let image_width = self.width as usize;
+        let image_height = match self.height {
+            Some(height) => height as usize,
+            None => ((image_width as f32 / ASPECT_RATIO) as usize).max(2),
+        };
+
+        RenderSettings {
+            image_width,
+            image_height,
+            samples_per_pixel: self.spp,
+            max_depth: self.max_depth,
+            russian_roulette_depth: self.russian_roulette_depth,
+            seed: self.seed,
+            clamp: self.clamp,
+            sample_lights: !self.no_light_sampling,
+            aovs: self.aovs || self.denoise,
+            adaptive_sampling: self.adaptive_threshold.map(|threshold| AdaptiveSampling {
+                min_samples: self.min_spp,
+                threshold,
+            }),
+            tile_size: self.tile_size as usize,
+            tile_order: self.tile_order,
+            filter: self.filter(),
+            sampler: self.sampler,
+        }
+    }
+}
+
+/// Parses a duration in seconds, minutes or hours, such as `1.5h`.
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let (number, unit_seconds) = match value.char_indices().last() {
+        Some((index, 's')) => (&value[..index], 1.0),
+        Some((index, 'm')) => (&value[..index], 60.0),
+        Some((index, 'h')) => (&value[..index], 3600.0),
+        _ => (value, 1.0),
+    };
+
+    number
+        .parse::<f64>()
+        .ok()
+        .and_then(|number| Duration::try_from_secs_f64(number * unit_seconds).ok())
+        .ok_or_else(|| format!("`{value}` is not a duration such as `90s`, `5m` or `2h`"))
+}
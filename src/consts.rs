@@ -4,5 +4,7 @@ pub const IMAGE_HEIGHT: usize = (IMAGE_WIDTH as f32 / ASPECT_RATIO) as usize;
 pub const VIEWPORT_HEIGHT: f32 = 2.0;
 pub const VIEWPORT_WIDTH: f32 = ASPECT_RATIO * VIEWPORT_HEIGHT;
 pub const FOCAL_LENGTH: f32 = 1.0;
-pub const SAMPLES_PER_PIXEL: i32 = 200;
+pub const SAMPLES_PER_PIXEL: u32 = 200;
 pub const MAX_DEPTH: u32 = 30;
+pub const RUSSIAN_ROULETTE_DEPTH: u32 = 3;
+pub const TILE_SIZE: usize = 32;
@@ -0,0 +1,229 @@
+use crate::aov::AovPixel;
+use crate::framebuffer::Framebuffer;
+use crate::math::color::Color;
+use rayon::prelude::*;
+use tracy_full::zone;
+
+/// Albedo below which a channel is filtered as is rather than divided by it.
+const MIN_ALBEDO: f32 = 0.01;
+
+/// Settings of the joint bilateral filter of [`denoise`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DenoiseSettings {
+    /// Half width, in pixels, of the square of neighbours averaged with each
+    /// pixel.
+    pub radius: usize,
+    /// Standard deviation of the weights along the distance, in pixels.
+    pub sigma_spatial: f32,
+    /// Tolerance on the relative difference of the luminances, once averaged
+    /// over 3x3 pixels, which keeps the edges of the shadows.
+    pub sigma_luminance: f32,
+    pub sigma_albedo: f32,
+    /// Tolerance on one minus the cosine between the normals.
+    pub sigma_normal: f32,
+    /// Tolerance on the relative difference of the depths.
+    pub sigma_depth: f32,
+}
+
+impl Default for DenoiseSettings {
+    fn default() -> Self {
+        Self {
+            radius: 5,
+            sigma_spatial: 3.0,
+            sigma_luminance: 1.0,
+            sigma_albedo: 0.1,
+            sigma_normal: 0.1,
+            sigma_depth: 0.05,
+        }
+    }
+}
+
+/// Denoises the radiance of the framebuffer with a joint bilateral filter.
+///
+/// Neighbours are weighted by their distance, their luminance and, when the
+/// framebuffer has [`Aovs`](crate::aov::Aovs), by the similarity of their
+/// first hits. The lighting is filtered divided by the albedo so that the
+/// textures stay sharp. The denoised framebuffer has no AOVs.
+pub fn denoise(framebuffer: &Framebuffer, settings: &DenoiseSettings) -> Framebuffer {
+    zone!();
+    let (width, height) = (framebuffer.width(), framebuffer.height());
+    let aovs = framebuffer.aovs().map(|aovs| aovs.pixels());
+    let albedo = |index: usize| aovs.map_or(Color::white(), |aovs| aovs[index].albedo);
+
+    let lighting: Vec<Color> = framebuffer
+        .pixels()
+        .iter()
+        .enumerate()
+        .map(|(index, pixel)| demodulate(*pixel, albedo(index)))
+        .collect();
+    let luminances = box_luminances(&lighting, width, height);
+    let radius = settings.radius;
+
+    let pixels = (0..height)
+        .into_par_iter()
+        .flat_map_iter(|y| {
+            let lighting = &lighting;
+            let luminances = &luminances;
+            (0..width).map(move |x| {
+                let center = y * width + x;
+                let mut sum = Color::black();
+                let mut weight_sum = 0.0;
+
+                for ny in y.saturating_sub(radius)..(y + radius + 1).min(height) {
+                    for nx in x.saturating_sub(radius)..(x + radius + 1).min(width) {
+                        let neighbour = ny * width + nx;
+                        let dx = nx as f32 - x as f32;
+                        let dy = ny as f32 - y as f32;
+                        let mut weight = gaussian(dx.hypot(dy), settings.sigma_spatial)
+                            * gaussian(
+                                relative_difference(luminances[center], luminances[neighbour]),
+                                settings.sigma_luminance,
+                            );
+                        if let Some(aovs) = aovs {
+                            weight *= hit_similarity(&aovs[center], &aovs[neighbour], settings);
+                        }
+
+                        sum += lighting[neighbour] * weight;
+                        weight_sum += weight;
+                    }
+                }
+
+                // The center has a weight of one, so the sum is positive.
+                modulate(sum * (1.0 / weight_sum), albedo(center))
+            })
+        })
+        .collect();
+
+    Framebuffer::from_pixels(width, height, pixels)
+}
+
+/// Weight of a neighbour hitting `other` against a pixel hitting `hit`.
+fn hit_similarity(hit: &AovPixel, other: &AovPixel, settings: &DenoiseSettings) -> f32 {
+    // Pixels seeing the background only match each other.
+    if hit.depth.is_finite() != other.depth.is_finite() {
+        return 0.0;
+    }
+    if !hit.depth.is_finite() {
+        return 1.0;
+    }
+
+    let albedo_distance = (0..3)
+        .map(|k| (hit.albedo[k] - other.albedo[k]).powi(2))
+        .sum::<f32>()
+        .sqrt();
+    let normal_distance = (1.0 - hit.normal.dot(other.normal)).max(0.0);
+    let depth_distance = (hit.depth - other.depth).abs() / hit.depth.max(f32::EPSILON);
+
+    gaussian(albedo_distance, settings.sigma_albedo)
+        * (-normal_distance / settings.sigma_normal).exp()
+        * (-depth_distance / settings.sigma_depth).exp()
+}
+
+fn gaussian(distance: f32, sigma: f32) -> f32 {
+    (-0.5 * (distance / sigma).powi(2)).exp()
+}
+
+fn relative_difference(a: f32, b: f32) -> f32 {
+    let sum = a.abs() + b.abs();
+    if sum > 0.0 {
+        (a - b).abs() / sum
+    } else {
+        0.0
+    }
+}
+
+/// Mean luminance of the 3x3 pixels around each pixel, less noisy than the
+/// luminance of the pixel alone.
+fn box_luminances(pixels: &[Color], width: usize, height: usize) -> Vec<f32> {
+    (0..height)
+        .flat_map(|y| {
+            (0..width).map(move |x| {
+                let rows = y.saturating_sub(1)..(y + 2).min(height);
+                let columns = x.saturating_sub(1)..(x + 2).min(width);
+                let count = rows.len() * columns.len();
+                let sum: f32 = rows
+                    .flat_map(|ny| columns.clone().map(move |nx| pixels[ny * width + nx]))
+                    .map(|pixel| pixel.luminance())
+                    .sum();
+
+                sum / count as f32
+            })
+        })
+        .collect()
+}
+
+fn demodulate(color: Color, albedo: Color) -> Color {
+    let channel = |k: usize| {
+        if albedo[k] > MIN_ALBEDO {
+            color[k] / albedo[k]
+        } else {
+            color[k]
+        }
+    };
+
+    Color::new(channel(0), channel(1), channel(2))
+}
+
+fn modulate(color: Color, albedo: Color) -> Color {
+    let channel = |k: usize| {
+        if albedo[k] > MIN_ALBEDO {
+            color[k] * albedo[k]
+        } else {
+            color[k]
+        }
+    };
+
+    Color::new(channel(0), channel(1), channel(2))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aov::{AovPixel, Aovs};
+    use crate::denoise::{denoise, DenoiseSettings};
+    use crate::framebuffer::Framebuffer;
+    use crate::math::color::Color;
+    use glam::Vec3A;
+    use rand::Rng;
+    use rand_xoshiro::rand_core::SeedableRng;
+
+    #[test]
+    fn denoise_removes_noise_and_keeps_albedo_edges() {
+        let (width, height) = (32, 32);
+        let albedo = |x: usize| {
+            if x < width / 2 {
+                Color::new(0.2, 0.2, 0.2)
+            } else {
+                Color::new(0.8, 0.8, 0.8)
+            }
+        };
+        let mut rng = rand_xoshiro::Xoshiro256Plus::seed_from_u64(5);
+        let noisy = (0..width * height)
+            .map(|index| albedo(index % width) * rng.gen_range(0.0..2.0))
+            .collect();
+        let aovs = (0..width * height)
+            .map(|index| AovPixel {
+                albedo: albedo(index % width),
+                normal: Vec3A::Z,
+                depth: 1.0,
+                object_id: 0,
+                emission: Color::black(),
+            })
+            .collect();
+        let framebuffer = Framebuffer::from_pixels(width, height, noisy)
+            .with_aovs(Aovs::from_pixels(width, height, aovs));
+
+        let denoised = denoise(&framebuffer, &DenoiseSettings::default());
+
+        let error = |framebuffer: &Framebuffer| {
+            (0..width * height)
+                .map(|index| (framebuffer.pixels()[index].x - albedo(index % width).x).powi(2))
+                .sum::<f32>()
+        };
+        assert!(denoised.aovs().is_none());
+        assert!(error(&denoised) < 0.1 * error(&framebuffer));
+        for y in 0..height {
+            assert!((denoised.pixel(width / 2 - 1, y).x - 0.2).abs() < 0.1);
+            assert!((denoised.pixel(width / 2, y).x - 0.8).abs() < 0.3);
+        }
+    }
+}
@@ -0,0 +1,501 @@
+use crate::checkpoint::{CheckpointError, CheckpointReader, CheckpointWriter};
+use crate::framebuffer::Framebuffer;
+use crate::renderer::{ProgressiveRenderer, RenderSettings};
+use crate::scene::Scene;
+use crate::scene_description::{SceneDescription, SceneDescriptionError};
+use crate::texture::Texture;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::{Display, Formatter};
+use std::io::{BufReader, BufWriter};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// First message of the coordinator, changed with the protocol.
+const PROTOCOL: &[u8] = b"raytracing distributed render 3";
+
+/// Delay between two checks for new workers or for tiles to hand out.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Time after which a silent peer is considered lost, the tiles of a lost
+/// worker being handed to the other workers.
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Delay between the messages keeping a connection alive while the other
+/// side waits, well below [`TIMEOUT`].
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Reply of the coordinator to a request of tiles while the remaining tiles
+/// are handed to other workers, one of which can fail.
+const NO_TILE_YET: u32 = u32::MAX;
+
+/// Maximum size of the scene description, of the settings and of each image.
+const MAX_MESSAGE_LEN: usize = 256 << 20;
+
+#[derive(Debug)]
+pub enum DistributedError {
+    Io(std::io::Error),
+    /// The other side closed the connection in the middle of a message.
+    Disconnected,
+    /// The other side sent nothing for [`TIMEOUT`].
+    TimedOut,
+    /// The other side is not a coordinator or worker of this version, or sent
+    /// invalid messages.
+    Protocol(String),
+    Scene(SceneDescriptionError),
+}
+
+impl Display for DistributedError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DistributedError::Io(err) => write!(f, "{err}"),
+            DistributedError::Disconnected => write!(f, "The connection was closed"),
+            DistributedError::TimedOut => {
+                write!(f, "Nothing was received for {} seconds", TIMEOUT.as_secs())
+            }
+            DistributedError::Protocol(message) => write!(f, "Invalid message : {message}"),
+            DistributedError::Scene(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for DistributedError {}
+
+impl From<std::io::Error> for DistributedError {
+    fn from(err: std::io::Error) -> Self {
+        match err.kind() {
+            // The kind of the read timeouts depends on the platform.
+            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut => Self::TimedOut,
+            _ => Self::Io(err),
+        }
+    }
+}
+
+impl From<CheckpointError> for DistributedError {
+    fn from(err: CheckpointError) -> Self {
+        match err {
+            CheckpointError::Io(err) => err.into(),
+            CheckpointError::Truncated => Self::Disconnected,
+            CheckpointError::NotACheckpoint | CheckpointError::OtherSettings => {
+                Self::Protocol("the samples are not in the format of this version".to_string())
+            }
+            CheckpointError::UndescribableScene(err) => Self::Scene(err),
+        }
+    }
+}
+
+impl From<SceneDescriptionError> for DistributedError {
+    fn from(err: SceneDescriptionError) -> Self {
+        Self::Scene(err)
+    }
+}
+
+type Reader = CheckpointReader<BufReader<TcpStream>>;
+type Writer = CheckpointWriter<BufWriter<TcpStream>>;
+
+fn connection(stream: TcpStream) -> Result<(Reader, Writer), DistributedError> {
+    stream.set_nodelay(true)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let reader = CheckpointReader::new(BufReader::new(stream.try_clone()?));
+
+    Ok((reader, CheckpointWriter::new(BufWriter::new(stream))))
+}
+
+/// Reads a message written by [`CheckpointWriter::bytes`], failing before
+/// allocating it when it is longer than `max_len`.
+fn read_message(reader: &mut Reader, max_len: usize) -> Result<Vec<u8>, DistributedError> {
+    let len = reader.u32()? as usize;
+    if len > max_len {
+        return Err(DistributedError::Protocol(format!(
+            "a message of {len} bytes is longer than {max_len} bytes"
+        )));
+    }
+
+    Ok(reader.exact_bytes(len)?)
+}
+
+fn read_string(reader: &mut Reader) -> Result<String, DistributedError> {
+    String::from_utf8(read_message(reader, MAX_MESSAGE_LEN)?)
+        .map_err(|err| DistributedError::Protocol(err.to_string()))
+}
+
+/// Sends the pixels of the image textures, which the scene description only
+/// names by path.
+fn write_images(
+    writer: &mut Writer,
+    images: &BTreeMap<String, Texture>,
+) -> Result<(), DistributedError> {
+    let images: Vec<_> = images
+        .iter()
+        .filter_map(|(path, texture)| match texture {
+            Texture::Image {
+                data,
+                width,
+                height,
+                ..
+            } => Some((path, data, *width, *height)),
+            _ => None,
+        })
+        .collect();
+    writer.u32(images.len() as u32)?;
+    for (path, data, width, height) in images {
+        writer.bytes(path.as_bytes())?;
+        writer.u32(width as u32)?;
+        writer.u32(height as u32)?;
+        writer.bytes(data)?;
+    }
+
+    Ok(())
+}
+
+fn read_images(reader: &mut Reader) -> Result<BTreeMap<String, Texture>, DistributedError> {
+    let count = reader.u32()?;
+    (0..count)
+        .map(|_| {
+            let path = read_string(reader)?;
+            let width = reader.u32()? as usize;
+            let height = reader.u32()? as usize;
+            let data = read_message(reader, MAX_MESSAGE_LEN)?;
+            let image = Texture::new_image_data(path.clone(), data, width, height);
+            Ok((path, image))
+        })
+        .collect()
+}
+
+/// Tiles of a distributed render, shared by the threads serving the workers.
+struct Coordinator<'a> {
+    renderer: Mutex<ProgressiveRenderer<'a>>,
+    /// Indices of the tiles not handed to a worker.
+    pending: Mutex<VecDeque<usize>>,
+    finished: AtomicUsize,
+    tile_count: usize,
+    /// Size of the samples of each tile.
+    tile_lens: Vec<usize>,
+    description: String,
+    images: BTreeMap<String, Texture>,
+    settings: String,
+}
+
+impl Coordinator<'_> {
+    fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Acquire) == self.tile_count
+    }
+
+    /// Hands out tiles to a worker until the render is finished.
+    fn serve(&self, stream: TcpStream) -> Result<(), DistributedError> {
+        let (mut reader, mut writer) = connection(stream)?;
+        writer.bytes(PROTOCOL)?;
+        writer.bytes(self.description.as_bytes())?;
+        write_images(&mut writer, &self.images)?;
+        writer.bytes(self.settings.as_bytes())?;
+        writer.flush()?;
+
+        loop {
+            // The worker keeps the connection alive with empty requests while
+            // it prepares the scene.
+            let requested = loop {
+                match reader.u32()? {
+                    0 => continue,
+                    requested => break requested as usize,
+                }
+            };
+            // The tiles of the other workers can come back if they fail.
+            let mut last_heartbeat = Instant::now();
+            let tiles = loop {
+                let tiles: Vec<usize> = {
+                    let mut pending = self.pending.lock().unwrap();
+                    let count = requested.min(pending.len());
+                    pending.drain(..count).collect()
+                };
+                if !tiles.is_empty() || self.is_finished() {
+                    break tiles;
+                }
+                if last_heartbeat.elapsed() >= HEARTBEAT_INTERVAL {
+                    writer.u32(NO_TILE_YET)?;
+                    writer.flush()?;
+                    last_heartbeat = Instant::now();
+                }
+                thread::sleep(POLL_INTERVAL);
+            };
+
+            if tiles.is_empty() {
+                writer.u32(0)?;
+                writer.flush()?;
+                return Ok(());
+            }
+            let mut tiles = VecDeque::from(tiles);
+            if let Err(err) = self.render_tiles(&mut tiles, &mut reader, &mut writer) {
+                let mut pending = self.pending.lock().unwrap();
+                for index in tiles.into_iter().rev() {
+                    pending.push_front(index);
+                }
+                return Err(err);
+            }
+        }
+    }
+
+    /// Sends the tiles to the worker and merges the samples it sends back,
+    /// removing the merged tiles.
+    fn render_tiles(
+        &self,
+        tiles: &mut VecDeque<usize>,
+        reader: &mut Reader,
+        writer: &mut Writer,
+    ) -> Result<(), DistributedError> {
+        writer.u32(tiles.len() as u32)?;
+        for &index in tiles.iter() {
+            writer.u32(index as u32)?;
+        }
+        writer.flush()?;
+
+        while let Some(&index) = tiles.front() {
+            // Read before locking the render, which the other workers merge
+            // their tiles into.
+            let samples = read_message(reader, self.tile_lens[index])?;
+            // Empty messages keep the connection alive while the worker
+            // renders.
+            if samples.is_empty() {
+                continue;
+            }
+            if samples.len() != self.tile_lens[index] {
+                return Err(DistributedError::Protocol(format!(
+                    "the samples of tile {index} are {} bytes instead of {}",
+                    samples.len(),
+                    self.tile_lens[index]
+                )));
+            }
+            let mut samples = CheckpointReader::new(samples.as_slice());
+            self.renderer
+                .lock()
+                .unwrap()
+                .read_tile(index, &mut samples)
+                .map_err(|_| DistributedError::Protocol(format!("tile {index} is truncated")))?;
+            tiles.pop_front();
+            self.finished.fetch_add(1, Ordering::Release);
+        }
+
+        Ok(())
+    }
+}
+
+/// Renders the scene on the workers connecting to `listener`, which give the
+/// same image as [`crate::renderer::render`].
+///
+/// Each worker receives the [`SceneDescription`] of the scene, the pixels of
+/// its image textures and the settings. It then requests as many tiles as it
+/// has threads, and sends back all their samples, until there is no tile left. The render waits for workers until
+/// every tile is rendered, and a worker failure is printed before its tiles
+/// are handed to the other workers. A worker silent for [`TIMEOUT`] has
+/// failed, the workers sending empty messages while they render.
+pub fn render_distributed(
+    scene: &Scene,
+    settings: &RenderSettings,
+    listener: &TcpListener,
+) -> Result<Framebuffer, DistributedError> {
+    let (description, images) = SceneDescription::from_scene_with_images(scene)?;
+    let description = description.to_toml_string()?;
+    let renderer = ProgressiveRenderer::new(scene, settings);
+    let tile_count = renderer.tile_count();
+    let tile_lens = (0..tile_count)
+        .map(|index| renderer.tile_len(index))
+        .collect();
+    let coordinator = Coordinator {
+        renderer: Mutex::new(renderer),
+        pending: Mutex::new((0..tile_count).collect()),
+        finished: AtomicUsize::new(0),
+        tile_count,
+        tile_lens,
+        description,
+        images,
+        settings: toml::to_string(settings)
+            .map_err(|err| DistributedError::Protocol(err.to_string()))?,
+    };
+
+    listener.set_nonblocking(true)?;
+    let accepted = thread::scope(|scope| {
+        while !coordinator.is_finished() {
+            match listener.accept() {
+                Ok((stream, address)) => {
+                    stream.set_nonblocking(false)?;
+                    let coordinator = &coordinator;
+                    scope.spawn(move || {
+                        if let Err(err) = coordinator.serve(stream) {
+                            eprintln!("Worker {address} failed : {err}");
+                        }
+                    });
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {
+                    thread::sleep(POLL_INTERVAL);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    });
+    listener.set_nonblocking(false)?;
+    accepted?;
+
+    Ok(coordinator.renderer.into_inner().unwrap().framebuffer())
+}
+
+/// Renders the tiles handed out by the coordinator listening at `address`,
+/// until its render is finished.
+pub fn run_worker(address: impl ToSocketAddrs) -> Result<(), DistributedError> {
+    let (mut reader, mut writer) = connection(TcpStream::connect(address)?)?;
+    match read_message(&mut reader, PROTOCOL.len()) {
+        Ok(protocol) if protocol == PROTOCOL => {}
+        Ok(_) | Err(DistributedError::Protocol(_)) => {
+            return Err(DistributedError::Protocol(
+                "the server is not a render coordinator of this version".to_string(),
+            ));
+        }
+        Err(err) => return Err(err),
+    }
+    let description = SceneDescription::parse(&read_string(&mut reader)?)?;
+    let images = read_images(&mut reader)?;
+    let settings: RenderSettings = toml::from_str(&read_string(&mut reader)?)
+        .map_err(|err| DistributedError::Protocol(err.to_string()))?;
+    let scene = keep_alive(&mut writer, || description.to_scene_with_images(&images))??;
+    let mut renderer = ProgressiveRenderer::new(&scene, &settings);
+
+    loop {
+        writer.u32(rayon::current_num_threads() as u32)?;
+        writer.flush()?;
+        let count = loop {
+            match reader.u32()? {
+                NO_TILE_YET => continue,
+                count => break count,
+            }
+        };
+        let tiles = (0..count)
+            .map(|_| match reader.u32()? as usize {
+                index if index < renderer.tile_count() => Ok(index),
+                index => Err(DistributedError::Protocol(format!("no tile {index}"))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if tiles.is_empty() {
+            return Ok(());
+        }
+
+        keep_alive(&mut writer, || renderer.render_tiles(&tiles))?;
+        for &index in &tiles {
+            let mut samples = CheckpointWriter::new(Vec::new());
+            renderer.write_tile(index, &mut samples)?;
+            writer.bytes(&samples.into_inner())?;
+        }
+        writer.flush()?;
+    }
+}
+
+/// Runs `work` on another thread, sending empty messages to the coordinator
+/// until it is done.
+fn keep_alive<T: Send>(
+    writer: &mut Writer,
+    work: impl FnOnce() -> T + Send,
+) -> Result<T, DistributedError> {
+    thread::scope(|scope| {
+        let work = scope.spawn(work);
+        let mut last_heartbeat = Instant::now();
+        while !work.is_finished() {
+            if last_heartbeat.elapsed() >= HEARTBEAT_INTERVAL {
+                writer.u32(0)?;
+                writer.flush()?;
+                last_heartbeat = Instant::now();
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+
+        Ok(work.join().unwrap())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::camera::Camera;
+    use crate::distributed::{render_distributed, run_worker, DistributedError};
+    use crate::film::Filter;
+    use crate::geometry::hittable_world::HittableWorld;
+    use crate::geometry::sphere::Sphere;
+    use crate::material::Material;
+    use crate::math::color::Color;
+    use crate::renderer::{render, RenderSettings};
+    use crate::scene::Scene;
+    use crate::texture::Texture;
+    use glam::Vec3A;
+    use std::io::Write;
+    use std::net::TcpListener;
+
+    #[test]
+    fn distributed_render_matches_local_render() {
+        let scene = Scene::bench_three_spheres();
+        let settings = RenderSettings {
+            samples_per_pixel: 4,
+            max_depth: 4,
+            aovs: true,
+            tile_size: 8,
+            filter: Filter::Tent { radius: 1.0 },
+            ..RenderSettings::new(40, 24)
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let distributed = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..2)
+                .map(|_| scope.spawn(move || run_worker(address)))
+                .collect();
+            let framebuffer = render_distributed(&scene, &settings, &listener).unwrap();
+            for worker in workers {
+                worker.join().unwrap().unwrap();
+            }
+            framebuffer
+        });
+
+        assert_eq!(distributed, render(&scene, &settings));
+    }
+
+    #[test]
+    fn image_textures_are_sent_to_the_workers() {
+        // The image has no file, so the worker can only use the sent pixels.
+        let pixels = vec![255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255];
+        let image = Texture::new_image_data("no_such_image.png".to_string(), pixels, 2, 2);
+        let mut world = HittableWorld::new();
+        world.add_sphere(Sphere::new(
+            Vec3A::ZERO,
+            2.0,
+            Material::new_lambertian(image),
+        ));
+        world.init_bvh_nodes();
+        let scene = Scene::new(world, Camera::default(), Color::new(0.7, 0.8, 1.0));
+        let settings = RenderSettings {
+            samples_per_pixel: 2,
+            max_depth: 4,
+            ..RenderSettings::new(24, 16)
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let worker = std::thread::spawn(move || run_worker(address));
+        let distributed = render_distributed(&scene, &settings, &listener).unwrap();
+        worker.join().unwrap().unwrap();
+
+        assert_eq!(distributed, render(&scene, &settings));
+    }
+
+    #[test]
+    fn worker_rejects_oversized_messages() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let worker = std::thread::spawn(move || run_worker(address));
+
+        let (mut coordinator, _) = listener.accept().unwrap();
+        coordinator.write_all(&u32::MAX.to_le_bytes()).unwrap();
+
+        assert!(matches!(
+            worker.join().unwrap(),
+            Err(DistributedError::Protocol(_))
+        ));
+    }
+}
@@ -0,0 +1,348 @@
+use crate::checkpoint::{CheckpointError, CheckpointReader, CheckpointWriter};
+use crate::math::color::Color;
+use crate::tile::Tile;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+use std::io::{Read, Write};
+
+/// Reconstruction filter weighting the samples around the center of each
+/// pixel, applied separately along each axis.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Filter {
+    /// Equal weights within `radius`.
+    Box { radius: f32 },
+    /// Weights decreasing linearly to zero at `radius`.
+    Tent { radius: f32 },
+    /// Gaussian of standard deviation `sigma`, lowered to reach zero at
+    /// `radius`.
+    Gaussian { radius: f32, sigma: f32 },
+    /// Mitchell-Netravali cubic of parameters `b` and `c`, stretched from two
+    /// pixels to `radius`.
+    Mitchell { radius: f32, b: f32, c: f32 },
+    /// Sinc windowed by a sinc stretched to `radius`, its number of lobes.
+    Lanczos { radius: f32 },
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter::Box { radius: 0.5 }
+    }
+}
+
+impl Filter {
+    /// Distance, in pixels along each axis, beyond which the weights are zero.
+    pub fn radius(&self) -> f32 {
+        match *self {
+            Filter::Box { radius }
+            | Filter::Tent { radius }
+            | Filter::Gaussian { radius, .. }
+            | Filter::Mitchell { radius, .. }
+            | Filter::Lanczos { radius } => radius,
+        }
+    }
+
+    /// Gets the weight in a pixel of a sample `dx`, `dy` pixels before its
+    /// center, which is negative in the lobes of the Mitchell-Netravali and
+    /// Lanczos filters.
+    ///
+    /// The weights are zero outside of `]-radius, radius]`, so that a sample
+    /// on the edge of two pixels counts only once with the box filter.
+    pub fn evaluate(&self, dx: f32, dy: f32) -> f32 {
+        self.evaluate_axis(dx) * self.evaluate_axis(dy)
+    }
+
+    fn evaluate_axis(&self, offset: f32) -> f32 {
+        if offset <= -self.radius() || offset > self.radius() {
+            return 0.0;
+        }
+        let distance = offset.abs();
+
+        match *self {
+            Filter::Box { .. } => 1.0,
+            Filter::Tent { radius } => 1.0 - distance / radius,
+            Filter::Gaussian { radius, sigma } => {
+                let gaussian = |x: f32| (-x * x / (2.0 * sigma * sigma)).exp();
+                (gaussian(distance) - gaussian(radius)).max(0.0)
+            }
+            Filter::Mitchell { radius, b, c } => mitchell(2.0 * distance / radius, b, c),
+            Filter::Lanczos { radius } => sinc(distance) * sinc(distance / radius),
+        }
+    }
+}
+
+/// Mitchell-Netravali cubic at `x` between zero and two.
+fn mitchell(x: f32, b: f32, c: f32) -> f32 {
+    let x2 = x * x;
+    let x3 = x2 * x;
+    let value = if x < 1.0 {
+        (12.0 - 9.0 * b - 6.0 * c) * x3 + (-18.0 + 12.0 * b + 6.0 * c) * x2 + (6.0 - 2.0 * b)
+    } else {
+        (-b - 6.0 * c) * x3
+            + (6.0 * b + 30.0 * c) * x2
+            + (-12.0 * b - 48.0 * c) * x
+            + (8.0 * b + 24.0 * c)
+    };
+
+    value / 6.0
+}
+
+fn sinc(x: f32) -> f32 {
+    if x < 1e-5 {
+        return 1.0;
+    }
+
+    (PI * x).sin() / (PI * x)
+}
+
+/// Weighted sums of the samples splatted to the pixels of a rectangle, row by
+/// row from its top left corner.
+#[derive(Debug, Clone, PartialEq)]
+struct WeightedPixels {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+    sums: Vec<Color>,
+    weights: Vec<f32>,
+}
+
+impl WeightedPixels {
+    fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+            sums: vec![Color::black(); width * height],
+            weights: vec![0.0; width * height],
+        }
+    }
+
+    /// Adds the sample to each pixel of the rectangle whose center is within
+    /// the radius of the filter.
+    fn add_sample(
+        &mut self,
+        filter: &Filter,
+        (x, y): (usize, usize),
+        (offset_x, offset_y): (f32, f32),
+        color: Color,
+    ) {
+        let margin = filter.radius().ceil() as usize;
+        let columns =
+            x.saturating_sub(margin).max(self.x)..(x + margin + 1).min(self.x + self.width);
+        let rows = y.saturating_sub(margin).max(self.y)..(y + margin + 1).min(self.y + self.height);
+
+        for row in rows {
+            // Computed from the pixels rather than from the positions on the
+            // film, which lose precision far from the corner.
+            let dy = row as f32 - y as f32 + 0.5 - offset_y;
+            for column in columns.clone() {
+                let weight = filter.evaluate(column as f32 - x as f32 + 0.5 - offset_x, dy);
+                if weight != 0.0 {
+                    let index = (row - self.y) * self.width + column - self.x;
+                    self.sums[index] += color * weight;
+                    self.weights[index] += weight;
+                }
+            }
+        }
+    }
+}
+
+/// Image receiving the samples of a render, each weighted by the
+/// reconstruction filter of its distance to the pixel centers around it.
+///
+/// The samples are located by their pixel, from the top left corner of the
+/// image, and their offset from the top left corner of this pixel, between
+/// zero and one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Film {
+    filter: Filter,
+    pixels: WeightedPixels,
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize, filter: Filter) -> Self {
+        Self {
+            filter,
+            pixels: WeightedPixels::new(0, 0, width, height),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.pixels.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.pixels.height
+    }
+
+    pub fn filter(&self) -> Filter {
+        self.filter
+    }
+
+    pub fn add_sample(&mut self, pixel: (usize, usize), offset: (f32, f32), color: Color) {
+        self.pixels.add_sample(&self.filter, pixel, offset, color);
+    }
+
+    /// Creates the part of the film receiving the samples of `tile`, which
+    /// extends beyond it by the radius of the filter.
+    pub fn tile(&self, tile: &Tile) -> FilmTile {
+        let margin = self.filter.radius().ceil() as usize;
+        let x = tile.x.saturating_sub(margin);
+        let y = tile.y.saturating_sub(margin);
+        let right = (tile.x + tile.width + margin).min(self.width());
+        let bottom = (tile.y + tile.height + margin).min(self.height());
+
+        FilmTile {
+            filter: self.filter,
+            pixels: WeightedPixels::new(x, y, right - x, bottom - y),
+        }
+    }
+
+    /// Adds the samples of a tile of this film.
+    pub fn merge_tile(&mut self, tile: &FilmTile) {
+        let tile = &tile.pixels;
+        for row in 0..tile.height {
+            for column in 0..tile.width {
+                let index = (tile.y + row) * self.width() + tile.x + column;
+                self.pixels.sums[index] += tile.sums[row * tile.width + column];
+                self.pixels.weights[index] += tile.weights[row * tile.width + column];
+            }
+        }
+    }
+
+    /// Gets the weighted mean of the samples of each pixel, black for the
+    /// pixels without samples.
+    pub fn pixels(&self) -> Vec<Color> {
+        self.pixels
+            .sums
+            .iter()
+            .zip(&self.pixels.weights)
+            .map(|(sum, weight)| {
+                if weight.abs() > f32::EPSILON {
+                    *sum * (1.0 / weight)
+                } else {
+                    Color::black()
+                }
+            })
+            .collect()
+    }
+}
+
+/// Part of a [`Film`] receiving the samples of one tile from a single thread,
+/// merged into the film afterwards.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilmTile {
+    filter: Filter,
+    pixels: WeightedPixels,
+}
+
+impl FilmTile {
+    /// Adds a sample like [`Film::add_sample`], which should be in the tile
+    /// the film tile was created for.
+    pub fn add_sample(&mut self, pixel: (usize, usize), offset: (f32, f32), color: Color) {
+        self.pixels.add_sample(&self.filter, pixel, offset, color);
+    }
+
+    /// Number of bytes written by [`FilmTile::write_checkpoint`], the sum
+    /// and the weight of each pixel.
+    pub(crate) fn checkpoint_len(&self) -> usize {
+        self.pixels.weights.len() * 4 * size_of::<f32>()
+    }
+
+    pub(crate) fn write_checkpoint(
+        &self,
+        writer: &mut CheckpointWriter<impl Write>,
+    ) -> Result<(), CheckpointError> {
+        for (sum, weight) in self.pixels.sums.iter().zip(&self.pixels.weights) {
+            writer.color(*sum)?;
+            writer.f32(*weight)?;
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the samples of the tile by the ones of a checkpoint of the
+    /// same tile.
+    pub(crate) fn read_checkpoint(
+        &mut self,
+        reader: &mut CheckpointReader<impl Read>,
+    ) -> Result<(), CheckpointError> {
+        for (sum, weight) in self.pixels.sums.iter_mut().zip(&mut self.pixels.weights) {
+            *sum = reader.color()?;
+            *weight = reader.f32()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::film::{Film, Filter};
+    use crate::math::color::Color;
+    use crate::tile::{tiles, TileOrder};
+
+    #[test]
+    fn filters_are_normalized_around_the_center() {
+        let filters = [
+            Filter::Box { radius: 0.5 },
+            Filter::Tent { radius: 1.0 },
+            Filter::Gaussian {
+                radius: 1.5,
+                sigma: 0.5,
+            },
+            Filter::Mitchell {
+                radius: 2.0,
+                b: 1.0 / 3.0,
+                c: 1.0 / 3.0,
+            },
+            Filter::Lanczos { radius: 2.0 },
+        ];
+
+        for filter in filters {
+            assert!(filter.evaluate(0.0, 0.0) > 0.0, "{filter:?}");
+            assert_eq!(filter.evaluate(-filter.radius(), 0.0), 0.0, "{filter:?}");
+            assert_eq!(filter.evaluate(0.3, -0.2), filter.evaluate(-0.3, 0.2));
+
+            // A constant image stays constant wherever the samples are.
+            let mut film = Film::new(6, 6, filter);
+            for k in 0..36 * 16 {
+                let pixel = ((k % 24) / 4, (k / 24) / 4);
+                let offset = (
+                    (k % 4) as f32 * 0.25 + 0.1,
+                    (k / 24 % 4) as f32 * 0.25 + 0.1,
+                );
+                film.add_sample(pixel, offset, Color::new(0.5, 0.5, 0.5));
+            }
+            for pixel in film.pixels() {
+                assert!((pixel.x - 0.5).abs() < 1e-4, "{filter:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn tiles_merge_to_the_whole_film() {
+        let filter = Filter::Tent { radius: 1.5 };
+        let offset = (0.3, 0.7);
+        let color = |(x, y): (usize, usize)| Color::new(x as f32, y as f32, 1.0);
+
+        let mut whole = Film::new(20, 15, filter);
+        for pixel in (0..20 * 15).map(|k| (k % 20, k / 20)) {
+            whole.add_sample(pixel, offset, color(pixel));
+        }
+        let mut merged = Film::new(20, 15, filter);
+        for tile in tiles(20, 15, 8, TileOrder::Scanline) {
+            let mut film_tile = merged.tile(&tile);
+            for index in 0..tile.pixel_count() {
+                let pixel = tile.pixel_position(index);
+                film_tile.add_sample(pixel, offset, color(pixel));
+            }
+            merged.merge_tile(&film_tile);
+        }
+
+        for (a, b) in whole.pixels().iter().zip(merged.pixels()) {
+            assert!((a.x - b.x).abs() < 1e-5 && (a.y - b.y).abs() < 1e-5);
+        }
+    }
+}
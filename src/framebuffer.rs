@@ -0,0 +1,141 @@
+use crate::aov::Aovs;
+use crate::math::color::Color;
+use crate::tone_mapping::{srgb_oetf, ToneMapping};
+
+/// Linear radiance of each pixel of a rendered image, row by row from the
+/// top left corner.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+    aovs: Option<Aovs>,
+    sample_counts: Option<Vec<u32>>,
+}
+
+impl Framebuffer {
+    /// Creates a black framebuffer.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self::from_pixels(width, height, vec![Color::black(); width * height])
+    }
+
+    /// # Panics
+    ///
+    /// If there is not exactly one pixel per position of the image.
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert_eq!(
+            pixels.len(),
+            width * height,
+            "A framebuffer needs one pixel per position"
+        );
+
+        Self {
+            width,
+            height,
+            pixels,
+            aovs: None,
+            sample_counts: None,
+        }
+    }
+
+    /// # Panics
+    ///
+    /// If the output variables do not have the size of the image.
+    pub fn with_aovs(mut self, aovs: Aovs) -> Self {
+        assert!(
+            aovs.width() == self.width && aovs.height() == self.height,
+            "The output variables need the size of the image"
+        );
+        self.aovs = Some(aovs);
+        self
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    pub fn aovs(&self) -> Option<&Aovs> {
+        self.aovs.as_ref()
+    }
+
+    /// # Panics
+    ///
+    /// If there is not exactly one count per pixel.
+    pub fn with_sample_counts(mut self, sample_counts: Vec<u32>) -> Self {
+        assert_eq!(
+            sample_counts.len(),
+            self.pixels.len(),
+            "A framebuffer needs one sample count per pixel"
+        );
+        self.sample_counts = Some(sample_counts);
+        self
+    }
+
+    /// Number of samples averaged in each pixel, when known.
+    pub fn sample_counts(&self) -> Option<&[u32]> {
+        self.sample_counts.as_deref()
+    }
+
+    /// Removes the output variables, to only write the radiance.
+    pub fn take_aovs(&mut self) -> Option<Aovs> {
+        self.aovs.take()
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+
+    /// Colors each pixel by its number of samples, from dark blue for the
+    /// fewest samples of the image to red for the most.
+    pub fn sample_heatmap(&self) -> Option<Framebuffer> {
+        let counts = self.sample_counts.as_ref()?;
+        let min = counts.iter().copied().min().unwrap_or(0);
+        let max = counts.iter().copied().max().unwrap_or(0);
+        let range = (max - min).max(1) as f32;
+
+        let pixels = counts
+            .iter()
+            .map(|count| heat_color((count - min) as f32 / range))
+            .collect();
+        Some(Self::from_pixels(self.width, self.height, pixels))
+    }
+
+    /// Encodes the pixels to 8 bits sRGB, after scaling them by `exposure`
+    /// stops and mapping them to the displayable range.
+    pub fn to_srgb8(&self, exposure: f32, tone_mapping: ToneMapping) -> Vec<u8> {
+        let scale = exposure.exp2();
+
+        self.pixels
+            .iter()
+            .flat_map(|pixel| {
+                let mapped = tone_mapping.apply(*pixel * scale);
+                (0..3).map(move |k| (255.0 * srgb_oetf(mapped[k]) + 0.5) as u8)
+            })
+            .collect()
+    }
+}
+
+/// Maps a value between zero and one to a blue, cyan, green, yellow and red
+/// ramp.
+fn heat_color(value: f32) -> Color {
+    const RAMP: [Color; 5] = [
+        Color::new(0.0, 0.0, 0.5),
+        Color::new(0.0, 0.8, 1.0),
+        Color::new(0.1, 0.9, 0.1),
+        Color::new(1.0, 0.9, 0.0),
+        Color::new(1.0, 0.0, 0.0),
+    ];
+
+    let position = value.clamp(0.0, 1.0) * (RAMP.len() - 1) as f32;
+    let index = (position as usize).min(RAMP.len() - 2);
+    let t = position - index as f32;
+    RAMP[index] * (1.0 - t) + RAMP[index + 1] * t
+}
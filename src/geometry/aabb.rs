@@ -25,14 +25,30 @@ impl Aabb {
         self.maximum
     }
 
-    pub fn hit(&self, ray: &Ray, mut t_min: f32, mut t_max: f32) -> bool {
+    pub fn surface_area(&self) -> f32 {
+        let size = self.maximum - self.minimum;
+        2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
+
+    pub fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
+        self.hit_inverse(ray.origin(), 1.0 / ray.direction(), t_min, t_max)
+    }
+
+    /// Same as [`Aabb::hit`], with the inverse of the ray direction computed
+    /// once for all the boxes a ray is tested against.
+    pub fn hit_inverse(
+        &self,
+        origin: Vec3A,
+        inverse_direction: Vec3A,
+        mut t_min: f32,
+        mut t_max: f32,
+    ) -> bool {
         zone!();
         for i in 0..3 {
-            let inverse_direction = 1.0 / ray.direction()[i];
-            let mut t0 = (self.min()[i] - ray.origin()[i]) * inverse_direction;
-            let mut t1 = (self.max()[i] - ray.origin()[i]) * inverse_direction;
+            let mut t0 = (self.min()[i] - origin[i]) * inverse_direction[i];
+            let mut t1 = (self.max()[i] - origin[i]) * inverse_direction[i];
 
-            if inverse_direction < 0.0 {
+            if inverse_direction[i] < 0.0 {
                 std::mem::swap(&mut t0, &mut t1);
             }
 
@@ -71,6 +71,18 @@ impl AabbBox {
             ],
         }
     }
+
+    pub fn box_min(&self) -> Vec3A {
+        self.box_min
+    }
+
+    pub fn box_max(&self) -> Vec3A {
+        self.box_max
+    }
+
+    pub fn material(&self) -> &Material {
+        self.sides_xy[0].material()
+    }
 }
 
 impl Hittable for AabbBox {
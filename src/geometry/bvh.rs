@@ -1,5 +1,23 @@
 use crate::geometry::aabb::Aabb;
 use crate::geometry::hittable_world::HittableObjectIndex;
+use std::fmt;
+
+/// Cost of testing a ray against the box of a node, relative to the cost of
+/// intersecting a primitive.
+pub const SAH_TRAVERSAL_COST: f32 = 0.125;
+pub const SAH_INTERSECTION_COST: f32 = 1.0;
+
+/// Algorithm used to build the BVH of a
+/// [`HittableWorld`](super::hittable_world::HittableWorld).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BvhBuilder {
+    /// Splits at the median along a random axis, with one primitive per leaf.
+    RandomMedian,
+    /// Splits where the surface area heuristic is the lowest, evaluated on
+    /// bins of the primitive centroids. Up to `max_leaf_size` primitives are
+    /// kept in a leaf when it is cheaper than splitting them.
+    Sah { max_leaf_size: usize },
+}
 
 #[derive(Debug)]
 pub struct BvhNode {
@@ -22,3 +40,89 @@ impl BvhNode {
         Self { left, right, aabb }
     }
 }
+
+/// Primitives `first..first + count` of the leaf objects of a world.
+#[derive(Debug)]
+pub struct BvhLeaf {
+    first: usize,
+    count: usize,
+    aabb: Aabb,
+}
+
+impl BvhLeaf {
+    pub fn new(first: usize, count: usize, aabb: Aabb) -> Self {
+        Self { first, count, aabb }
+    }
+
+    pub fn first(&self) -> usize {
+        self.first
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn aabb(&self) -> &Aabb {
+        &self.aabb
+    }
+}
+
+/// Shape of a BVH, to compare builders.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct BvhStats {
+    /// Number of inner nodes.
+    pub node_count: usize,
+    pub leaf_count: usize,
+    pub max_leaf_size: usize,
+    pub max_depth: usize,
+    /// Expected cost of a ray hitting the root box, the sum of the costs of
+    /// the nodes and leaves weighted by their area relative to the root.
+    pub sah_cost: f32,
+}
+
+impl fmt::Display for BvhStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} nodes, {} leaves of at most {} primitives, depth {}, SAH cost {:.2}",
+            self.node_count, self.leaf_count, self.max_leaf_size, self.max_depth, self.sah_cost
+        )
+    }
+}
+
+/// Node of the BVH compiled for traversal, stored in depth-first order so
+/// that the first child of an inner node is the node following it.
+#[derive(Debug)]
+pub struct FlatBvhNode {
+    aabb: Aabb,
+    kind: FlatBvhNodeKind,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum FlatBvhNodeKind {
+    Inner {
+        second_child: u32,
+        /// Axis along which the first child is before the second one.
+        axis: u8,
+    },
+    /// Primitives `first..first + count` of the flattened primitives.
+    Leaf { first: u32, count: u32 },
+}
+
+impl FlatBvhNode {
+    pub fn new(aabb: Aabb, kind: FlatBvhNodeKind) -> Self {
+        Self { aabb, kind }
+    }
+
+    pub fn aabb(&self) -> &Aabb {
+        &self.aabb
+    }
+
+    pub fn kind(&self) -> FlatBvhNodeKind {
+        self.kind
+    }
+
+    pub(crate) fn set_kind(&mut self, kind: FlatBvhNodeKind) {
+        self.kind = kind;
+    }
+}
@@ -1,7 +1,7 @@
 use crate::geometry::aabb::Aabb;
 use crate::material::Material;
 use crate::ray::Ray;
-use glam::Vec3A;
+use glam::{Affine3A, Mat3A, Vec3A};
 
 #[derive(Debug)]
 pub struct HitRecord<'a> {
@@ -46,6 +46,23 @@ impl<'a> HitRecord<'a> {
         self.normal
     }
 
+    /// Replaces the normal used for shading, the side of the surface that was
+    /// hit is still the one computed from the geometric normal.
+    pub fn set_shading_normal(&mut self, outward_normal: Vec3A) {
+        self.normal = if self.front_face {
+            outward_normal
+        } else {
+            -outward_normal
+        };
+    }
+
+    /// Moves the record from the space of an instance to the world space,
+    /// the side of the surface that was hit stays the same.
+    pub fn transform(&mut self, transform: &Affine3A, normal_matrix: &Mat3A) {
+        self.point = transform.transform_point3a(self.point);
+        self.normal = (*normal_matrix * self.normal).normalize();
+    }
+
     pub fn point(&self) -> Vec3A {
         self.point
     }
@@ -76,7 +93,7 @@ pub trait Hittable {
     fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb>;
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum HittableObjectType {
     Sphere,
     MovingSphere,
@@ -84,5 +101,9 @@ pub enum HittableObjectType {
     XzRectangle,
     YzRectangle,
     AabbBox,
+    Triangle,
+    MeshTriangle,
+    Instance,
     BvhNode,
+    BvhLeaf,
 }
@@ -1,18 +1,26 @@
 use crate::geometry::aabb::Aabb;
 use crate::geometry::aabb_box::AabbBox;
-use crate::geometry::bvh::BvhNode;
+use crate::geometry::bvh::{
+    BvhBuilder, BvhLeaf, BvhNode, BvhStats, FlatBvhNode, FlatBvhNodeKind, SAH_INTERSECTION_COST,
+    SAH_TRAVERSAL_COST,
+};
 use crate::geometry::hit::{HitRecord, Hittable, HittableObjectType};
+use crate::geometry::instance::Instance;
 use crate::geometry::moving_sphere::MovingSphere;
 use crate::geometry::sphere::Sphere;
+use crate::geometry::triangle::Triangle;
+use crate::geometry::triangle_mesh::TriangleMesh;
 use crate::geometry::xy_rectangle::XyRectangle;
 use crate::geometry::xz_rectangle::XzRectangle;
 use crate::geometry::yz_rectangle::YzRectangle;
 use crate::ray::Ray;
+use glam::Vec3A;
 use rand::Rng;
 use rand_xoshiro::rand_core::SeedableRng;
 use std::cmp::Ordering;
+use tracy_full::zone;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct HittableObjectIndex {
     pub object_type: HittableObjectType,
     pub index: usize,
@@ -24,6 +32,30 @@ impl HittableObjectIndex {
     }
 }
 
+/// Triangle of one of the meshes of a [`HittableWorld`].
+#[derive(Copy, Clone, Debug)]
+struct MeshTriangleIndex {
+    mesh: usize,
+    triangle: usize,
+}
+
+/// Maximum depth of a BVH, the size of the stack of nodes left to visit.
+const BVH_STACK_SIZE: usize = 64;
+
+/// Number of bins the centroids are sorted into by the SAH builder.
+const SAH_BIN_COUNT: usize = 12;
+
+/// Depth from which the SAH builder splits at the median, so that the BVH of
+/// the up to `u32::MAX` objects of a flat BVH fits in [`BVH_STACK_SIZE`].
+const SAH_MAX_DEPTH: usize = BVH_STACK_SIZE - u32::BITS as usize - 1;
+
+/// Primitive and its bounds while a BVH is built.
+struct BuildPrimitive {
+    index: HittableObjectIndex,
+    aabb: Aabb,
+    centroid: Vec3A,
+}
+
 pub struct HittableWorld {
     spheres: Vec<Sphere>,
     moving_spheres: Vec<MovingSphere>,
@@ -31,13 +63,26 @@ pub struct HittableWorld {
     xz_rectangles: Vec<XzRectangle>,
     yz_rectangles: Vec<YzRectangle>,
     aabb_boxes: Vec<AabbBox>,
+    triangles: Vec<Triangle>,
+    triangle_meshes: Vec<TriangleMesh>,
+    mesh_triangles: Vec<MeshTriangleIndex>,
+    instances: Vec<Instance>,
     bvh_nodes: Vec<BvhNode>,
-    first_node_index: usize,
+    bvh_leaves: Vec<BvhLeaf>,
+    leaf_objects: Vec<HittableObjectIndex>,
+    bvh_root: Option<HittableObjectIndex>,
+    flat_bvh_nodes: Vec<FlatBvhNode>,
+    flat_bvh_objects: Vec<HittableObjectIndex>,
     rng: rand_xoshiro::Xoshiro256Plus,
 }
 
 impl HittableWorld {
     pub fn new() -> Self {
+        Self::with_seed(0)
+    }
+
+    /// Creates an empty world whose BVH split axes are drawn from `seed`.
+    pub fn with_seed(seed: u64) -> Self {
         Self {
             spheres: Vec::new(),
             moving_spheres: Vec::new(),
@@ -45,9 +90,17 @@ impl HittableWorld {
             xz_rectangles: Vec::new(),
             yz_rectangles: Vec::new(),
             aabb_boxes: Vec::new(),
+            triangles: Vec::new(),
+            triangle_meshes: Vec::new(),
+            mesh_triangles: Vec::new(),
+            instances: Vec::new(),
             bvh_nodes: Vec::new(),
-            first_node_index: 0,
-            rng: rand_xoshiro::Xoshiro256Plus::from_entropy(),
+            bvh_leaves: Vec::new(),
+            leaf_objects: Vec::new(),
+            bvh_root: None,
+            flat_bvh_nodes: Vec::new(),
+            flat_bvh_objects: Vec::new(),
+            rng: rand_xoshiro::Xoshiro256Plus::seed_from_u64(seed),
         }
     }
 
@@ -75,6 +128,61 @@ impl HittableWorld {
         self.aabb_boxes.push(aabb_box);
     }
 
+    pub fn add_triangle(&mut self, triangle: Triangle) {
+        self.triangles.push(triangle);
+    }
+
+    pub fn add_triangle_mesh(&mut self, mesh: TriangleMesh) {
+        let mesh_index = self.triangle_meshes.len();
+        self.mesh_triangles.extend(
+            (0..mesh.triangle_count()).map(|triangle| MeshTriangleIndex {
+                mesh: mesh_index,
+                triangle,
+            }),
+        );
+        self.triangle_meshes.push(mesh);
+    }
+
+    pub fn add_instance(&mut self, instance: Instance) {
+        self.instances.push(instance);
+    }
+
+    pub fn spheres(&self) -> &[Sphere] {
+        &self.spheres
+    }
+
+    pub fn moving_spheres(&self) -> &[MovingSphere] {
+        &self.moving_spheres
+    }
+
+    pub fn xy_rectangles(&self) -> &[XyRectangle] {
+        &self.xy_rectangles
+    }
+
+    pub fn xz_rectangles(&self) -> &[XzRectangle] {
+        &self.xz_rectangles
+    }
+
+    pub fn yz_rectangles(&self) -> &[YzRectangle] {
+        &self.yz_rectangles
+    }
+
+    pub fn aabb_boxes(&self) -> &[AabbBox] {
+        &self.aabb_boxes
+    }
+
+    pub fn triangles(&self) -> &[Triangle] {
+        &self.triangles
+    }
+
+    pub fn triangle_meshes(&self) -> &[TriangleMesh] {
+        &self.triangle_meshes
+    }
+
+    pub fn instances(&self) -> &[Instance] {
+        &self.instances
+    }
+
     pub fn len(&self) -> usize {
         self.spheres.len()
             + self.moving_spheres.len()
@@ -82,6 +190,9 @@ impl HittableWorld {
             + self.xz_rectangles.len()
             + self.yz_rectangles.len()
             + self.aabb_boxes.len()
+            + self.triangles.len()
+            + self.mesh_triangles.len()
+            + self.instances.len()
     }
 
     pub fn clear(&mut self) {
@@ -91,6 +202,10 @@ impl HittableWorld {
         self.xz_rectangles.clear();
         self.yz_rectangles.clear();
         self.aabb_boxes.clear();
+        self.triangles.clear();
+        self.triangle_meshes.clear();
+        self.mesh_triangles.clear();
+        self.instances.clear();
     }
 
     pub fn hit_no_limit(&self, ray: &Ray) -> Option<HitRecord> {
@@ -105,12 +220,6 @@ impl HittableWorld {
         t_max: f32,
     ) -> Option<HitRecord> {
         match hittable_object_index.object_type {
-            HittableObjectType::BvhNode => self.hit_node(
-                &self.bvh_nodes[hittable_object_index.index],
-                ray,
-                t_min,
-                t_max,
-            ),
             HittableObjectType::Sphere => {
                 self.spheres[hittable_object_index.index].hit(ray, t_min, t_max)
             }
@@ -129,6 +238,26 @@ impl HittableWorld {
             HittableObjectType::AabbBox => {
                 self.aabb_boxes[hittable_object_index.index].hit(ray, t_min, t_max)
             }
+            HittableObjectType::Triangle => {
+                self.triangles[hittable_object_index.index].hit(ray, t_min, t_max)
+            }
+            HittableObjectType::MeshTriangle => {
+                let mesh_triangle = self.mesh_triangles[hittable_object_index.index];
+                self.triangle_meshes[mesh_triangle.mesh].hit_triangle(
+                    mesh_triangle.triangle,
+                    ray,
+                    t_min,
+                    t_max,
+                )
+            }
+            HittableObjectType::Instance => {
+                self.instances[hittable_object_index.index].hit(ray, t_min, t_max)
+            }
+            // BVH nodes and leaves are only referenced by the tree built
+            // before flattening, hit_object walks the flat nodes instead.
+            HittableObjectType::BvhNode | HittableObjectType::BvhLeaf => {
+                unreachable!("BVH nodes are not hit through hit_at")
+            }
         }
     }
 
@@ -157,12 +286,122 @@ impl HittableWorld {
             HittableObjectType::BvhNode => {
                 Some(self.bvh_nodes[hittable_object_index.index].aabb().clone())
             }
+            HittableObjectType::BvhLeaf => {
+                Some(self.bvh_leaves[hittable_object_index.index].aabb().clone())
+            }
             HittableObjectType::AabbBox => {
                 self.aabb_boxes[hittable_object_index.index].bounding_box(time0, time1)
             }
+            HittableObjectType::Triangle => {
+                self.triangles[hittable_object_index.index].bounding_box(time0, time1)
+            }
+            HittableObjectType::MeshTriangle => {
+                let mesh_triangle = self.mesh_triangles[hittable_object_index.index];
+                Some(
+                    self.triangle_meshes[mesh_triangle.mesh]
+                        .triangle_bounding_box(mesh_triangle.triangle),
+                )
+            }
+            HittableObjectType::Instance => {
+                self.instances[hittable_object_index.index].bounding_box(time0, time1)
+            }
         }
     }
 
+    /// Finds the closest hit of the ray, with the index of the object that was
+    /// hit, an instance for the objects of its shared geometry.
+    pub fn hit_object(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+    ) -> Option<(HittableObjectIndex, HitRecord<'_>)> {
+        zone!();
+        if self.flat_bvh_nodes.is_empty() {
+            panic!("There should be nodes in the hittable list.");
+        }
+
+        let origin = ray.origin();
+        let inverse_direction = 1.0 / ray.direction();
+        let mut stack = [0u32; BVH_STACK_SIZE];
+        let mut stack_len = 0;
+        let mut current = 0;
+        let mut closest_distance = t_max;
+        let mut record = None;
+
+        loop {
+            let node = &self.flat_bvh_nodes[current];
+            if node
+                .aabb()
+                .hit_inverse(origin, inverse_direction, t_min, closest_distance)
+            {
+                match node.kind() {
+                    FlatBvhNodeKind::Inner { second_child, axis } => {
+                        // The nearest child is visited first, so that its hits
+                        // cull the farther one.
+                        if inverse_direction[axis as usize] < 0.0 {
+                            stack[stack_len] = current as u32 + 1;
+                            current = second_child as usize;
+                        } else {
+                            stack[stack_len] = second_child;
+                            current += 1;
+                        }
+                        stack_len += 1;
+                        continue;
+                    }
+                    FlatBvhNodeKind::Leaf { first, count } => {
+                        let objects =
+                            &self.flat_bvh_objects[first as usize..(first + count) as usize];
+                        for index in objects {
+                            if let Some(object_record) =
+                                self.hit_at(index, ray, t_min, closest_distance)
+                            {
+                                closest_distance = object_record.t();
+                                record = Some((*index, object_record));
+                            }
+                        }
+                    }
+                }
+            }
+
+            if stack_len == 0 {
+                return record;
+            }
+            stack_len -= 1;
+            current = stack[stack_len] as usize;
+        }
+    }
+
+    /// Gets a number identifying the object, unique among the objects of
+    /// the world, which are numbered by type in the order of their fields.
+    pub fn object_id(&self, index: &HittableObjectIndex) -> usize {
+        let counts = [
+            (HittableObjectType::Sphere, self.spheres.len()),
+            (HittableObjectType::MovingSphere, self.moving_spheres.len()),
+            (HittableObjectType::XyRectangle, self.xy_rectangles.len()),
+            (HittableObjectType::XzRectangle, self.xz_rectangles.len()),
+            (HittableObjectType::YzRectangle, self.yz_rectangles.len()),
+            (HittableObjectType::AabbBox, self.aabb_boxes.len()),
+            (HittableObjectType::Triangle, self.triangles.len()),
+            (HittableObjectType::MeshTriangle, self.mesh_triangles.len()),
+            (HittableObjectType::Instance, self.instances.len()),
+        ];
+        assert!(
+            counts
+                .iter()
+                .any(|(object_type, _)| *object_type == index.object_type),
+            "BVH nodes are not objects of the world"
+        );
+
+        let offset: usize = counts
+            .iter()
+            .take_while(|(object_type, _)| *object_type != index.object_type)
+            .map(|(_, count)| count)
+            .sum();
+
+        offset + index.index
+    }
+
     pub fn is_empty(&self) -> bool {
         self.spheres.is_empty()
             && self.moving_spheres.is_empty()
@@ -170,31 +409,9 @@ impl HittableWorld {
             && self.xz_rectangles.is_empty()
             && self.yz_rectangles.is_empty()
             && self.aabb_boxes.is_empty()
-    }
-
-    fn hit_node(&self, node: &BvhNode, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
-        if !node.aabb().hit(ray, t_min, t_max) {
-            return None;
-        }
-
-        let record_left_option = self.hit_at(node.left(), ray, t_min, t_max);
-        let mut left_distance = t_max;
-        let mut record = None;
-        if let Some(record_left) = record_left_option {
-            left_distance = record_left.t();
-            record = Some(record_left);
-        }
-
-        let record_right = self.hit_at(node.right(), ray, t_min, left_distance);
-        if let Some(record_right) = record_right {
-            if left_distance < record_right.t() {
-                record
-            } else {
-                Some(record_right)
-            }
-        } else {
-            record
-        }
+            && self.triangles.is_empty()
+            && self.mesh_triangles.is_empty()
+            && self.instances.is_empty()
     }
 
     fn box_compare(
@@ -256,7 +473,17 @@ impl HittableWorld {
         HittableObjectIndex::new(HittableObjectType::BvhNode, self.bvh_nodes.len() - 1)
     }
 
+    /// Builds the BVH with [`BvhBuilder::RandomMedian`].
     pub fn init_bvh_nodes(&mut self) {
+        self.build_bvh(BvhBuilder::RandomMedian);
+    }
+
+    /// Builds the BVH of the objects of the world, replacing the previous one.
+    pub fn build_bvh(&mut self, builder: BvhBuilder) {
+        self.bvh_nodes.clear();
+        self.bvh_leaves.clear();
+        self.leaf_objects.clear();
+
         let mut hittables = Vec::new();
 
         for i in 0..self.spheres.len() {
@@ -286,8 +513,311 @@ impl HittableWorld {
             hittables.push(HittableObjectIndex::new(HittableObjectType::AabbBox, i));
         }
 
-        let node = self.create_node(&mut hittables[..], 0.0, 1.0);
-        self.first_node_index = node.index;
+        for i in 0..self.triangles.len() {
+            hittables.push(HittableObjectIndex::new(HittableObjectType::Triangle, i));
+        }
+
+        for i in 0..self.mesh_triangles.len() {
+            hittables.push(HittableObjectIndex::new(
+                HittableObjectType::MeshTriangle,
+                i,
+            ));
+        }
+
+        for i in 0..self.instances.len() {
+            hittables.push(HittableObjectIndex::new(HittableObjectType::Instance, i));
+        }
+
+        let root = match builder {
+            BvhBuilder::RandomMedian => self.create_node(&mut hittables[..], 0.0, 1.0),
+            BvhBuilder::Sah { max_leaf_size } => {
+                let mut primitives: Vec<_> = hittables
+                    .into_iter()
+                    .map(|index| {
+                        let aabb = self
+                            .get_aabb(index, 0.0, 1.0)
+                            .expect("no bounding box in bvh node");
+                        let centroid = (aabb.min() + aabb.max()) / 2.0;
+                        BuildPrimitive {
+                            index,
+                            aabb,
+                            centroid,
+                        }
+                    })
+                    .collect();
+                if primitives.is_empty() {
+                    panic!("0 Hittables provided to node creation");
+                }
+
+                self.create_sah_node(&mut primitives, max_leaf_size.max(1), 0)
+            }
+        };
+        self.bvh_root = Some(root);
+
+        self.flat_bvh_nodes.clear();
+        self.flat_bvh_objects.clear();
+        let depth = self.flatten_node(root);
+        assert!(
+            depth <= BVH_STACK_SIZE,
+            "The BVH is deeper than {BVH_STACK_SIZE} levels"
+        );
+    }
+
+    /// Appends the subtree of `index` to the flat BVH in depth-first order,
+    /// the child nearest to the origin of its axis first, and returns its
+    /// depth.
+    fn flatten_node(&mut self, index: HittableObjectIndex) -> usize {
+        let aabb = self
+            .get_aabb(index, 0.0, 1.0)
+            .expect("no bounding box in bvh node");
+        let node_index = self.flat_bvh_nodes.len();
+
+        let objects = match index.object_type {
+            HittableObjectType::BvhNode => {
+                let node = &self.bvh_nodes[index.index];
+                let (left, right) = (*node.left(), *node.right());
+                if left == right {
+                    vec![left]
+                } else {
+                    let center = |index| {
+                        let aabb: Aabb = self.get_aabb(index, 0.0, 1.0).unwrap();
+                        (aabb.min() + aabb.max()) / 2.0
+                    };
+                    let offset = center(right) - center(left);
+                    let axis = largest_axis(offset.abs());
+                    let (first, second) = if offset[axis] < 0.0 {
+                        (right, left)
+                    } else {
+                        (left, right)
+                    };
+
+                    self.flat_bvh_nodes.push(FlatBvhNode::new(
+                        aabb,
+                        FlatBvhNodeKind::Inner {
+                            second_child: 0,
+                            axis: axis as u8,
+                        },
+                    ));
+                    let first_depth = self.flatten_node(first);
+                    let second_child = self.flat_bvh_nodes.len() as u32;
+                    let second_depth = self.flatten_node(second);
+                    self.flat_bvh_nodes[node_index].set_kind(FlatBvhNodeKind::Inner {
+                        second_child,
+                        axis: axis as u8,
+                    });
+
+                    return 1 + first_depth.max(second_depth);
+                }
+            }
+            HittableObjectType::BvhLeaf => {
+                let leaf = &self.bvh_leaves[index.index];
+                self.leaf_objects[leaf.first()..leaf.first() + leaf.count()].to_vec()
+            }
+            _ => vec![index],
+        };
+
+        let first = self.flat_bvh_objects.len() as u32;
+        self.flat_bvh_objects.extend(objects);
+        let count = self.flat_bvh_objects.len() as u32 - first;
+        self.flat_bvh_nodes.push(FlatBvhNode::new(
+            aabb,
+            FlatBvhNodeKind::Leaf { first, count },
+        ));
+
+        1
+    }
+
+    fn create_sah_node(
+        &mut self,
+        primitives: &mut [BuildPrimitive],
+        max_leaf_size: usize,
+        depth: usize,
+    ) -> HittableObjectIndex {
+        if primitives.len() == 1 {
+            return primitives[0].index;
+        }
+
+        let aabb = primitives
+            .iter()
+            .map(|primitive| primitive.aabb.clone())
+            .reduce(Aabb::surrounding_box)
+            .unwrap();
+        let (centroid_min, centroid_max) = primitives.iter().fold(
+            (Vec3A::INFINITY, Vec3A::NEG_INFINITY),
+            |(min, max), primitive| (min.min(primitive.centroid), max.max(primitive.centroid)),
+        );
+        let extent = centroid_max - centroid_min;
+        let axis = largest_axis(extent);
+
+        let bin_of = |primitive: &BuildPrimitive| {
+            let offset = (primitive.centroid[axis] - centroid_min[axis]) / extent[axis];
+            ((offset * SAH_BIN_COUNT as f32) as usize).min(SAH_BIN_COUNT - 1)
+        };
+
+        let best_split = if extent[axis] > 0.0 && depth < SAH_MAX_DEPTH {
+            sah_best_split(primitives, &aabb, bin_of)
+        } else {
+            None
+        };
+
+        let leaf_cost = SAH_INTERSECTION_COST * primitives.len() as f32;
+        let mid = match best_split {
+            Some((_, cost)) if primitives.len() <= max_leaf_size && leaf_cost <= cost => {
+                return self.create_leaf(primitives, aabb);
+            }
+            Some((split, _)) => {
+                let mut mid = 0;
+                for i in 0..primitives.len() {
+                    if bin_of(&primitives[i]) <= split {
+                        primitives.swap(i, mid);
+                        mid += 1;
+                    }
+                }
+                mid
+            }
+            None if primitives.len() <= max_leaf_size => {
+                return self.create_leaf(primitives, aabb);
+            }
+            // Too deep for the SAH, which can split off a few primitives at
+            // each level, or the centroids are all at the same place.
+            None => {
+                let mid = primitives.len() / 2;
+                primitives.select_nth_unstable_by(mid, |a, b| {
+                    a.centroid[axis].total_cmp(&b.centroid[axis])
+                });
+                mid
+            }
+        };
+
+        let (left_primitives, right_primitives) = primitives.split_at_mut(mid);
+        let left = self.create_sah_node(left_primitives, max_leaf_size, depth + 1);
+        let right = self.create_sah_node(right_primitives, max_leaf_size, depth + 1);
+
+        self.bvh_nodes.push(BvhNode::new(left, right, aabb));
+        HittableObjectIndex::new(HittableObjectType::BvhNode, self.bvh_nodes.len() - 1)
+    }
+
+    fn create_leaf(&mut self, primitives: &[BuildPrimitive], aabb: Aabb) -> HittableObjectIndex {
+        let first = self.leaf_objects.len();
+        self.leaf_objects
+            .extend(primitives.iter().map(|primitive| primitive.index));
+        self.bvh_leaves
+            .push(BvhLeaf::new(first, primitives.len(), aabb));
+
+        HittableObjectIndex::new(HittableObjectType::BvhLeaf, self.bvh_leaves.len() - 1)
+    }
+
+    /// Describes the shape of the BVH, see [`BvhStats`].
+    pub fn bvh_stats(&self) -> BvhStats {
+        let mut stats = BvhStats::default();
+        if let Some(root) = self.bvh_root {
+            let root_area = self
+                .get_aabb(root, 0.0, 1.0)
+                .map_or(0.0, |aabb| aabb.surface_area());
+            self.collect_bvh_stats(root, 1, root_area, &mut stats);
+        }
+
+        stats
+    }
+
+    fn collect_bvh_stats(
+        &self,
+        index: HittableObjectIndex,
+        depth: usize,
+        root_area: f32,
+        stats: &mut BvhStats,
+    ) {
+        stats.max_depth = stats.max_depth.max(depth);
+        let area = self
+            .get_aabb(index, 0.0, 1.0)
+            .map_or(0.0, |aabb| aabb.surface_area());
+        let relative_area = if root_area > 0.0 {
+            area / root_area
+        } else {
+            1.0
+        };
+
+        let leaf_size = match index.object_type {
+            HittableObjectType::BvhNode => {
+                stats.node_count += 1;
+                stats.sah_cost += SAH_TRAVERSAL_COST * relative_area;
+
+                let node = &self.bvh_nodes[index.index];
+                self.collect_bvh_stats(*node.left(), depth + 1, root_area, stats);
+                // Nodes of a single object reference it twice.
+                if node.right() != node.left() {
+                    self.collect_bvh_stats(*node.right(), depth + 1, root_area, stats);
+                }
+                return;
+            }
+            HittableObjectType::BvhLeaf => self.bvh_leaves[index.index].count(),
+            _ => 1,
+        };
+
+        stats.leaf_count += 1;
+        stats.max_leaf_size = stats.max_leaf_size.max(leaf_size);
+        stats.sah_cost += SAH_INTERSECTION_COST * leaf_size as f32 * relative_area;
+    }
+}
+
+/// Returns the last bin of the left side of the cheapest split of
+/// `primitives` and its cost, if a split puts primitives on both sides.
+fn sah_best_split(
+    primitives: &[BuildPrimitive],
+    aabb: &Aabb,
+    bin_of: impl Fn(&BuildPrimitive) -> usize,
+) -> Option<(usize, f32)> {
+    let mut counts = [0usize; SAH_BIN_COUNT];
+    let mut boxes: [Option<Aabb>; SAH_BIN_COUNT] = Default::default();
+    for primitive in primitives {
+        let bin = bin_of(primitive);
+        counts[bin] += 1;
+        boxes[bin] = Aabb::opt_surrounding_box(boxes[bin].take(), Some(primitive.aabb.clone()));
+    }
+
+    // Cost of the primitives on the right of each split, from the last bin.
+    let mut right_costs = [0.0; SAH_BIN_COUNT];
+    let mut right_count = 0;
+    let mut right_box = None;
+    for bin in (1..SAH_BIN_COUNT).rev() {
+        right_count += counts[bin];
+        right_box = Aabb::opt_surrounding_box(right_box, boxes[bin].clone());
+        right_costs[bin - 1] = right_box
+            .as_ref()
+            .map_or(0.0, |aabb| aabb.surface_area() * right_count as f32);
+    }
+
+    let area = aabb.surface_area().max(f32::EPSILON);
+    let mut best_split = None;
+    let mut left_count = 0;
+    let mut left_box = None;
+    for split in 0..SAH_BIN_COUNT - 1 {
+        left_count += counts[split];
+        left_box = Aabb::opt_surrounding_box(left_box, boxes[split].clone());
+        if left_count == 0 || left_count == primitives.len() {
+            continue;
+        }
+
+        let left_cost = left_box
+            .as_ref()
+            .map_or(0.0, |aabb| aabb.surface_area() * left_count as f32);
+        let cost =
+            SAH_TRAVERSAL_COST + SAH_INTERSECTION_COST * (left_cost + right_costs[split]) / area;
+        if best_split.is_none_or(|(_, best_cost)| cost < best_cost) {
+            best_split = Some((split, cost));
+        }
+    }
+
+    best_split
+}
+
+fn largest_axis(vector: Vec3A) -> usize {
+    if vector.x >= vector.y && vector.x >= vector.z {
+        0
+    } else if vector.y >= vector.z {
+        1
+    } else {
+        2
     }
 }
 
@@ -317,13 +847,7 @@ fn get_objects_bounding_box<T: Hittable>(items: &Vec<T>, time0: f32, time1: f32)
 
 impl Hittable for HittableWorld {
     fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
-        let first = self.bvh_nodes.get(self.first_node_index);
-
-        if first.is_none() {
-            panic!("There should be nodes in the hittable list.");
-        }
-
-        self.hit_node(first.unwrap(), ray, t_min, t_max)
+        self.hit_object(ray, t_min, t_max).map(|(_, record)| record)
     }
 
     fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
@@ -337,20 +861,30 @@ impl Hittable for HittableWorld {
         let xz_rectangles_box = get_objects_bounding_box(&self.xz_rectangles, time0, time1);
         let yz_rectangles_box = get_objects_bounding_box(&self.yz_rectangles, time0, time1);
         let aabb_box_box = get_objects_bounding_box(&self.aabb_boxes, time0, time1);
+        let triangles_box = get_objects_bounding_box(&self.triangles, time0, time1);
+        let triangle_meshes_box = get_objects_bounding_box(&self.triangle_meshes, time0, time1);
+        let instances_box = get_objects_bounding_box(&self.instances, time0, time1);
 
         let a = Aabb::opt_surrounding_box(spheres_box, moving_spheres_box);
         let b = Aabb::opt_surrounding_box(a, xy_rectangles_box);
         let c = Aabb::opt_surrounding_box(b, xz_rectangles_box);
         let d = Aabb::opt_surrounding_box(c, yz_rectangles_box);
 
-        Aabb::opt_surrounding_box(d, aabb_box_box)
+        let e = Aabb::opt_surrounding_box(d, aabb_box_box);
+        let f = Aabb::opt_surrounding_box(e, triangles_box);
+
+        let g = Aabb::opt_surrounding_box(f, triangle_meshes_box);
+
+        Aabb::opt_surrounding_box(g, instances_box)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::geometry::bvh::BvhBuilder;
     use crate::geometry::hittable_world::HittableWorld;
     use crate::geometry::sphere::Sphere;
+    use crate::geometry::triangle_mesh::TriangleMesh;
     use crate::material::Material;
     use crate::ray::Ray;
     use glam::Vec3A;
@@ -371,4 +905,108 @@ mod tests {
 
         assert!(result.is_some());
     }
+
+    #[test]
+    fn hittable_world_hit_mesh_triangles() {
+        let mut hittable_list = HittableWorld::new();
+        let quad = TriangleMesh::new(
+            vec![
+                Vec3A::new(-1.0, -1.0, 5.0),
+                Vec3A::new(1.0, -1.0, 5.0),
+                Vec3A::new(1.0, 1.0, 5.0),
+                Vec3A::new(-1.0, 1.0, 5.0),
+            ],
+            Vec::new(),
+            Vec::new(),
+            vec![[0, 1, 2], [0, 2, 3]],
+            Material::new_dielectric(1.5),
+        );
+        hittable_list.add_triangle_mesh(quad);
+        hittable_list.add_sphere(Sphere::new(
+            Vec3A::new(0.0, 0.0, 10.0),
+            1.0,
+            Material::new_dielectric(1.5),
+        ));
+        hittable_list.init_bvh_nodes();
+
+        for x in [-0.5, 0.5] {
+            let ray = Ray::new(Vec3A::new(x, 0.25, 0.0), Vec3A::Z);
+            let result = hittable_list.hit_no_limit(&ray).unwrap();
+
+            assert!((result.t() - 5.0).abs() < 1e-5);
+        }
+
+        assert_eq!(hittable_list.len(), 3);
+    }
+
+    #[test]
+    fn sah_bvh_hits_like_median_bvh() {
+        let mut hittable_list = HittableWorld::new();
+        for i in 0..200 {
+            // Uneven sizes and spacing, like the small spheres over the ground of big_scene.
+            let x = (i % 20) as f32 * 1.5;
+            let z = (i / 20) as f32 * (1.0 + (i % 3) as f32);
+            let radius = 0.2 + (i % 7) as f32 * 0.1;
+            hittable_list.add_sphere(Sphere::new(
+                Vec3A::new(x, radius, z),
+                radius,
+                Material::new_dielectric(1.5),
+            ));
+        }
+        hittable_list.add_sphere(Sphere::new(
+            Vec3A::new(0.0, -1000.0, 0.0),
+            1000.0,
+            Material::new_dielectric(1.5),
+        ));
+
+        let rays: Vec<_> = (0..500)
+            .map(|i| {
+                let target = Vec3A::new((i % 25) as f32 * 1.2, 0.3, (i / 25) as f32 * 1.7);
+                let origin = Vec3A::new(15.0, 10.0, -20.0);
+                Ray::new(origin, (target - origin).normalize())
+            })
+            .collect();
+
+        hittable_list.init_bvh_nodes();
+        let median_hits: Vec<_> = rays
+            .iter()
+            .map(|ray| hittable_list.hit_no_limit(ray).map(|record| record.t()))
+            .collect();
+        let median_stats = hittable_list.bvh_stats();
+
+        hittable_list.build_bvh(BvhBuilder::Sah { max_leaf_size: 4 });
+        let sah_hits: Vec<_> = rays
+            .iter()
+            .map(|ray| hittable_list.hit_no_limit(ray).map(|record| record.t()))
+            .collect();
+        let sah_stats = hittable_list.bvh_stats();
+
+        assert_eq!(median_hits, sah_hits);
+        assert_eq!(median_stats.max_leaf_size, 1);
+        assert!(sah_stats.max_leaf_size <= 4);
+        assert!(sah_stats.sah_cost < median_stats.sah_cost);
+    }
+
+    #[test]
+    fn sah_bvh_of_exponentially_spaced_spheres_is_not_too_deep() {
+        let mut hittable_list = HittableWorld::new();
+        for i in -90..90 {
+            // The SAH splits off the farthest few spheres at each level.
+            let distance = 1.6f32.powi(i);
+            for axis in [Vec3A::X, Vec3A::Y, Vec3A::Z] {
+                hittable_list.add_sphere(Sphere::new(
+                    axis * distance,
+                    distance / 4.0,
+                    Material::new_dielectric(1.5),
+                ));
+            }
+        }
+
+        hittable_list.build_bvh(BvhBuilder::Sah { max_leaf_size: 1 });
+        let ray = Ray::new(Vec3A::new(1.0, 0.0, -10.0), Vec3A::Z);
+        let result = hittable_list.hit_no_limit(&ray).unwrap();
+
+        assert!((result.t() - 9.75).abs() < 1e-4);
+        assert!(hittable_list.bvh_stats().max_depth <= 64);
+    }
 }
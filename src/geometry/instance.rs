@@ -0,0 +1,131 @@
+use crate::geometry::aabb::Aabb;
+use crate::geometry::hit::{HitRecord, Hittable};
+use crate::geometry::hittable_world::HittableWorld;
+use crate::ray::Ray;
+use glam::{Affine3A, BVec3A, Mat3A, Vec3A};
+use std::sync::Arc;
+use tracy_full::zone;
+
+/// Shared geometry placed in the world by an affine transform.
+///
+/// Rays are moved into the space of the object rather than the object into
+/// the world, so any number of instances can reference the same
+/// [`HittableWorld`] without copying it.
+pub struct Instance {
+    object: Arc<HittableWorld>,
+    transform: Affine3A,
+    inverse_transform: Affine3A,
+    normal_matrix: Mat3A,
+    bounding_box: Option<Aabb>,
+}
+
+impl Instance {
+    /// Creates an instance of `object`, whose BVH must already be
+    /// initialized, transformed from its own space to the world by
+    /// `transform`.
+    pub fn new(object: Arc<HittableWorld>, transform: Affine3A) -> Self {
+        let inverse_transform = transform.inverse();
+        let normal_matrix = inverse_transform.matrix3.transpose();
+        let bounding_box = object
+            .bounding_box(0.0, 1.0)
+            .map(|aabb| transform_bounding_box(&transform, &aabb));
+
+        Self {
+            object,
+            transform,
+            inverse_transform,
+            normal_matrix,
+            bounding_box,
+        }
+    }
+
+    pub fn object(&self) -> &Arc<HittableWorld> {
+        &self.object
+    }
+
+    pub fn transform(&self) -> Affine3A {
+        self.transform
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        zone!();
+        // The direction is not normalized so that distances along the ray
+        // are the same in both spaces.
+        let mut object_ray = Ray::new(
+            self.inverse_transform.transform_point3a(ray.origin()),
+            self.inverse_transform.transform_vector3a(ray.direction()),
+        );
+        object_ray.time = ray.time;
+
+        let mut record = self.object.hit(&object_ray, t_min, t_max)?;
+        record.transform(&self.transform, &self.normal_matrix);
+
+        Some(record)
+    }
+
+    fn bounding_box(&self, _: f32, _: f32) -> Option<Aabb> {
+        self.bounding_box.clone()
+    }
+}
+
+fn transform_bounding_box(transform: &Affine3A, aabb: &Aabb) -> Aabb {
+    let corners = (0..8).map(|corner| {
+        Vec3A::select(
+            BVec3A::new(corner & 1 != 0, corner & 2 != 0, corner & 4 != 0),
+            aabb.max(),
+            aabb.min(),
+        )
+    });
+
+    let mut minimum = Vec3A::INFINITY;
+    let mut maximum = Vec3A::NEG_INFINITY;
+    for corner in corners {
+        let corner = transform.transform_point3a(corner);
+        minimum = minimum.min(corner);
+        maximum = maximum.max(corner);
+    }
+
+    Aabb::new(minimum, maximum)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geometry::aabb_box::AabbBox;
+    use crate::geometry::hit::Hittable;
+    use crate::geometry::hittable_world::HittableWorld;
+    use crate::geometry::instance::Instance;
+    use crate::material::Material;
+    use crate::ray::Ray;
+    use glam::{Affine3A, Quat, Vec3, Vec3A};
+    use std::sync::Arc;
+
+    #[test]
+    fn instance_hit_is_transformed() {
+        let mut cube = HittableWorld::new();
+        cube.add_aabb_box(AabbBox::new(
+            Vec3A::splat(-1.0),
+            Vec3A::ONE,
+            Material::new_dielectric(1.5),
+        ));
+        cube.init_bvh_nodes();
+
+        let transform = Affine3A::from_scale_rotation_translation(
+            Vec3::new(2.0, 1.0, 1.0),
+            Quat::from_rotation_z(std::f32::consts::FRAC_PI_2),
+            Vec3::new(0.0, 0.0, 10.0),
+        );
+        let instance = Instance::new(Arc::new(cube), transform);
+
+        // Rotated by a quarter turn, the cube is 4 units tall and 2 wide.
+        let ray = Ray::new(Vec3A::new(0.0, -10.0, 10.0), Vec3A::Y);
+        let record = instance.hit(&ray, 0.001, f32::INFINITY).unwrap();
+        assert!((record.t() - 8.0).abs() < 1e-4);
+        assert!((record.normal() + Vec3A::Y).length() < 1e-4);
+        assert!((record.point() - Vec3A::new(0.0, -2.0, 10.0)).length() < 1e-4);
+
+        let aabb = instance.bounding_box(0.0, 1.0).unwrap();
+        assert!((aabb.max() - Vec3A::new(1.0, 2.0, 11.0)).length() < 1e-4);
+    }
+}
@@ -3,8 +3,11 @@ pub mod aabb_box;
 pub mod bvh;
 pub mod hit;
 pub mod hittable_world;
+pub mod instance;
 pub mod moving_sphere;
 pub mod sphere;
+pub mod triangle;
+pub mod triangle_mesh;
 pub mod xy_rectangle;
 pub mod xz_rectangle;
 pub mod yz_rectangle;
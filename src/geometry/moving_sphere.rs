@@ -91,6 +91,30 @@ impl MovingSphere {
         }
     }
 
+    pub fn center0(&self) -> Vec3A {
+        self.center0
+    }
+
+    pub fn center1(&self) -> Vec3A {
+        self.center1
+    }
+
+    pub fn time0(&self) -> f32 {
+        self.time0
+    }
+
+    pub fn time1(&self) -> f32 {
+        self.time1
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
     pub fn center(&self, time: f32) -> Vec3A {
         self.center0
             + ((time - self.time0) / (self.time1 - self.time0)) * (self.center1 - self.center0)
@@ -22,6 +22,18 @@ impl Sphere {
         }
     }
 
+    pub fn center(&self) -> Vec3A {
+        self.center
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
     pub fn get_sphere_uv(p: &Vec3A) -> (f32, f32) {
         let theta = f32::acos(-p.y);
         let phi = f32::atan2(-p.z, p.x) + PI;
@@ -0,0 +1,209 @@
+use crate::geometry::aabb::Aabb;
+use crate::geometry::hit::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use glam::{Vec2, Vec3A};
+use tracy_full::zone;
+
+pub struct Triangle {
+    vertices: [Vec3A; 3],
+    normals: Option<[Vec3A; 3]>,
+    uvs: Option<[Vec2; 3]>,
+    material: Material,
+}
+
+impl Triangle {
+    /// Creates a flat shaded triangle, its front face is the one from which
+    /// the vertices are seen counter-clockwise.
+    pub fn new(vertices: [Vec3A; 3], material: Material) -> Self {
+        Self::new_with_attributes(vertices, None, None, material)
+    }
+
+    /// Creates a triangle with optional per-vertex normals, interpolated for
+    /// smooth shading, and texture coordinates.
+    ///
+    /// Without texture coordinates, the barycentric coordinates of the hit
+    /// are used as `u` and `v`.
+    pub fn new_with_attributes(
+        vertices: [Vec3A; 3],
+        normals: Option<[Vec3A; 3]>,
+        uvs: Option<[Vec2; 3]>,
+        material: Material,
+    ) -> Self {
+        Self {
+            vertices,
+            normals,
+            uvs,
+            material,
+        }
+    }
+
+    pub fn vertices(&self) -> [Vec3A; 3] {
+        self.vertices
+    }
+
+    pub fn normals(&self) -> Option<[Vec3A; 3]> {
+        self.normals
+    }
+
+    pub fn uvs(&self) -> Option<[Vec2; 3]> {
+        self.uvs
+    }
+
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        hit_triangle(
+            self.vertices,
+            self.normals,
+            self.uvs,
+            &self.material,
+            ray,
+            t_min,
+            t_max,
+        )
+    }
+
+    fn bounding_box(&self, _: f32, _: f32) -> Option<Aabb> {
+        Some(triangle_bounding_box(self.vertices))
+    }
+}
+
+/// Intersects a ray with a triangle using the Möller-Trumbore algorithm.
+pub(crate) fn hit_triangle<'a>(
+    vertices: [Vec3A; 3],
+    normals: Option<[Vec3A; 3]>,
+    uvs: Option<[Vec2; 3]>,
+    material: &'a Material,
+    ray: &Ray,
+    t_min: f32,
+    t_max: f32,
+) -> Option<HitRecord<'a>> {
+    zone!();
+    let edge1 = vertices[1] - vertices[0];
+    let edge2 = vertices[2] - vertices[0];
+    let p = ray.direction().cross(edge2);
+    let determinant = edge1.dot(p);
+    if determinant.abs() < 1e-8 {
+        return None;
+    }
+
+    let inverse_determinant = 1.0 / determinant;
+    let to_origin = ray.origin() - vertices[0];
+    let b1 = to_origin.dot(p) * inverse_determinant;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let q = to_origin.cross(edge1);
+    let b2 = ray.direction().dot(q) * inverse_determinant;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = edge2.dot(q) * inverse_determinant;
+    if t < t_min || t > t_max {
+        return None;
+    }
+
+    let b0 = 1.0 - b1 - b2;
+    let (u, v) = match uvs {
+        Some(uvs) => {
+            let uv = b0 * uvs[0] + b1 * uvs[1] + b2 * uvs[2];
+            (uv.x, uv.y)
+        }
+        None => (b1, b2),
+    };
+
+    let mut geometric_normal = edge1.cross(edge2).normalize();
+    let shading_normal = normals
+        .map(|normals| (b0 * normals[0] + b1 * normals[1] + b2 * normals[2]).normalize_or_zero());
+
+    // The vertex normals are trusted over the winding of the vertices.
+    if let Some(shading_normal) = shading_normal {
+        if shading_normal.dot(geometric_normal) < 0.0 {
+            geometric_normal = -geometric_normal;
+        }
+    }
+
+    let mut record = HitRecord::new(
+        ray.at(t),
+        t,
+        u,
+        v,
+        geometric_normal,
+        &ray.direction(),
+        material,
+    );
+
+    if let Some(shading_normal) = shading_normal.filter(|normal| *normal != Vec3A::ZERO) {
+        record.set_shading_normal(shading_normal);
+    }
+
+    Some(record)
+}
+
+pub(crate) fn triangle_bounding_box(vertices: [Vec3A; 3]) -> Aabb {
+    // Padded so that triangles aligned with an axis still have a volume.
+    const PADDING: Vec3A = Vec3A::splat(0.0001);
+    let minimum = vertices[0].min(vertices[1]).min(vertices[2]);
+    let maximum = vertices[0].max(vertices[1]).max(vertices[2]);
+
+    Aabb::new(minimum - PADDING, maximum + PADDING)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geometry::hit::Hittable;
+    use crate::geometry::triangle::Triangle;
+    use crate::material::Material;
+    use crate::ray::Ray;
+    use glam::{Vec2, Vec3A};
+
+    fn unit_triangle(normals: Option<[Vec3A; 3]>) -> Triangle {
+        Triangle::new_with_attributes(
+            [Vec3A::ZERO, Vec3A::X, Vec3A::Y],
+            normals,
+            Some([Vec2::ZERO, Vec2::new(1.0, 0.0), Vec2::new(0.0, 1.0)]),
+            Material::new_dielectric(1.5),
+        )
+    }
+
+    #[test]
+    fn triangle_hit_interpolates_uvs() {
+        let triangle = unit_triangle(None);
+        let ray = Ray::new(Vec3A::new(0.25, 0.5, 1.0), -Vec3A::Z);
+
+        let record = triangle.hit(&ray, 0.001, f32::INFINITY).unwrap();
+
+        assert!((record.t() - 1.0).abs() < 1e-6);
+        assert!((record.u() - 0.25).abs() < 1e-6);
+        assert!((record.v() - 0.5).abs() < 1e-6);
+        assert!(record.front_face());
+        assert_eq!(record.normal(), Vec3A::Z);
+    }
+
+    #[test]
+    fn triangle_hit_interpolates_normals() {
+        let tilted = Vec3A::new(1.0, 0.0, 1.0).normalize();
+        let triangle = unit_triangle(Some([Vec3A::Z, tilted, Vec3A::Z]));
+        let ray = Ray::new(Vec3A::new(0.5, 0.25, -1.0), Vec3A::Z);
+
+        let record = triangle.hit(&ray, 0.001, f32::INFINITY).unwrap();
+
+        assert!(!record.front_face());
+        assert!(record.normal().x < 0.0 && record.normal().z < 0.0);
+    }
+
+    #[test]
+    fn triangle_miss_outside_edges() {
+        let triangle = unit_triangle(None);
+        let ray = Ray::new(Vec3A::new(0.75, 0.75, 1.0), -Vec3A::Z);
+
+        assert!(triangle.hit(&ray, 0.001, f32::INFINITY).is_none());
+    }
+}
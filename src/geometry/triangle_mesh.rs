@@ -0,0 +1,144 @@
+use crate::geometry::aabb::Aabb;
+use crate::geometry::hit::{HitRecord, Hittable};
+use crate::geometry::triangle::{hit_triangle, triangle_bounding_box};
+use crate::material::Material;
+use crate::ray::Ray;
+use glam::{Vec2, Vec3A};
+
+/// Indexed triangle mesh sharing one material.
+///
+/// Normals and texture coordinates are optional and indexed like the
+/// positions. When added to a [`HittableWorld`](super::hittable_world::HittableWorld)
+/// every triangle is a separate leaf of the BVH.
+pub struct TriangleMesh {
+    positions: Vec<Vec3A>,
+    normals: Vec<Vec3A>,
+    uvs: Vec<Vec2>,
+    indices: Vec<[u32; 3]>,
+    material: Material,
+}
+
+impl TriangleMesh {
+    /// Creates a mesh from its vertex attributes and the vertex indices of
+    /// each triangle.
+    ///
+    /// # Panics
+    ///
+    /// If `normals` or `uvs` are neither empty nor as long as `positions`, or
+    /// if an index is out of the positions.
+    pub fn new(
+        positions: Vec<Vec3A>,
+        normals: Vec<Vec3A>,
+        uvs: Vec<Vec2>,
+        indices: Vec<[u32; 3]>,
+        material: Material,
+    ) -> Self {
+        assert!(
+            normals.is_empty() || normals.len() == positions.len(),
+            "A mesh needs one normal per vertex or none"
+        );
+        assert!(
+            uvs.is_empty() || uvs.len() == positions.len(),
+            "A mesh needs one texture coordinate per vertex or none"
+        );
+        assert!(
+            indices
+                .iter()
+                .flatten()
+                .all(|&i| (i as usize) < positions.len()),
+            "Triangle vertex index out of the mesh positions"
+        );
+
+        Self {
+            positions,
+            normals,
+            uvs,
+            indices,
+            material,
+        }
+    }
+
+    pub fn positions(&self) -> &[Vec3A] {
+        &self.positions
+    }
+
+    pub fn normals(&self) -> &[Vec3A] {
+        &self.normals
+    }
+
+    pub fn uvs(&self) -> &[Vec2] {
+        &self.uvs
+    }
+
+    pub fn indices(&self) -> &[[u32; 3]] {
+        &self.indices
+    }
+
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    pub fn set_material(&mut self, material: Material) {
+        self.material = material;
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn hit_triangle(
+        &self,
+        triangle: usize,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+    ) -> Option<HitRecord<'_>> {
+        let indices = self.indices[triangle].map(|i| i as usize);
+        let normals = if self.normals.is_empty() {
+            None
+        } else {
+            Some(indices.map(|i| self.normals[i]))
+        };
+        let uvs = if self.uvs.is_empty() {
+            None
+        } else {
+            Some(indices.map(|i| self.uvs[i]))
+        };
+
+        hit_triangle(
+            indices.map(|i| self.positions[i]),
+            normals,
+            uvs,
+            &self.material,
+            ray,
+            t_min,
+            t_max,
+        )
+    }
+
+    pub fn triangle_bounding_box(&self, triangle: usize) -> Aabb {
+        triangle_bounding_box(self.indices[triangle].map(|i| self.positions[i as usize]))
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let mut record_option = None;
+        let mut closest_distance = t_max;
+
+        for triangle in 0..self.indices.len() {
+            if let Some(record) = self.hit_triangle(triangle, ray, t_min, closest_distance) {
+                closest_distance = record.t();
+                record_option = Some(record);
+            }
+        }
+
+        record_option
+    }
+
+    fn bounding_box(&self, _: f32, _: f32) -> Option<Aabb> {
+        (0..self.indices.len())
+            .map(|triangle| self.triangle_bounding_box(triangle))
+            .reduce(Aabb::surrounding_box)
+    }
+}
@@ -25,6 +25,30 @@ impl XyRectangle {
             k,
         }
     }
+
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    pub fn x0(&self) -> f32 {
+        self.x0
+    }
+
+    pub fn x1(&self) -> f32 {
+        self.x1
+    }
+
+    pub fn y0(&self) -> f32 {
+        self.y0
+    }
+
+    pub fn y1(&self) -> f32 {
+        self.y1
+    }
+
+    pub fn k(&self) -> f32 {
+        self.k
+    }
 }
 
 impl Hittable for XyRectangle {
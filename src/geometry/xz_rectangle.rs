@@ -25,6 +25,30 @@ impl XzRectangle {
             k,
         }
     }
+
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    pub fn x0(&self) -> f32 {
+        self.x0
+    }
+
+    pub fn x1(&self) -> f32 {
+        self.x1
+    }
+
+    pub fn z0(&self) -> f32 {
+        self.z0
+    }
+
+    pub fn z1(&self) -> f32 {
+        self.z1
+    }
+
+    pub fn k(&self) -> f32 {
+        self.k
+    }
 }
 
 impl Hittable for XzRectangle {
@@ -25,6 +25,30 @@ impl YzRectangle {
             k,
         }
     }
+
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    pub fn y0(&self) -> f32 {
+        self.y0
+    }
+
+    pub fn y1(&self) -> f32 {
+        self.y1
+    }
+
+    pub fn z0(&self) -> f32 {
+        self.z0
+    }
+
+    pub fn z1(&self) -> f32 {
+        self.z1
+    }
+
+    pub fn k(&self) -> f32 {
+        self.k
+    }
 }
 
 impl Hittable for YzRectangle {
@@ -0,0 +1,520 @@
+use crate::camera::Camera;
+use crate::consts::ASPECT_RATIO;
+use crate::geometry::aabb::Aabb;
+use crate::geometry::hittable_world::HittableWorld;
+use crate::geometry::sphere::Sphere;
+use crate::geometry::triangle_mesh::TriangleMesh;
+use crate::material::Material;
+use crate::math::color::Color;
+use crate::scene::Scene;
+use crate::texture::Texture;
+use glam::{Mat3A, Mat4, Vec2, Vec3A};
+use gltf::camera::Projection;
+use gltf::image::{Format, Source};
+use gltf::khr_lights_punctual::Kind;
+use gltf::material::AlphaMode;
+use gltf::mesh::Mode;
+use gltf::{buffer, image, Document, Node};
+use std::f32::consts::PI;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use tracy_full::zone;
+
+/// Background of the scenes that have neither emissive materials nor lights.
+const SKY_COLOR: Color = Color::new(0.7, 0.8, 1.0);
+
+/// Radius of the spheres replacing punctual lights, relative to the radius
+/// of the scene.
+const LIGHT_RADIUS_RATIO: f32 = 0.01;
+
+/// Error produced while importing a glTF file.
+#[derive(Debug)]
+pub struct GltfError {
+    path: PathBuf,
+    message: String,
+}
+
+impl GltfError {
+    fn new(path: &Path, message: impl Into<String>) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            message: message.into(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for GltfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.message)
+    }
+}
+
+impl std::error::Error for GltfError {}
+
+/// Scene imported by [`load_gltf`], with the parts of the file that were
+/// skipped or approximated.
+pub struct GltfImport {
+    pub scene: Scene,
+    pub warnings: Vec<String>,
+}
+
+/// Imports the default scene of a `.gltf` or `.glb` file.
+///
+/// The node transforms are applied to the vertices of the meshes, which
+/// become [`TriangleMesh`]es. The camera is the first perspective camera of
+/// the node hierarchy, or one looking at the whole scene along -Z.
+///
+/// Metallic-roughness materials are approximated with [`Material`]:
+///
+/// * An emissive color makes a [`Material::DiffuseLight`].
+/// * A transmission, or a blended alpha below 0.5, makes a [`Material::Dielectric`].
+/// * A metallic factor of at least 0.5 makes a [`Material::Metal`] as fuzzy as it is rough.
+/// * Otherwise the base color, or its texture, makes a [`Material::Lambertian`].
+///
+/// Point and spot lights of `KHR_lights_punctual` become small emissive
+/// spheres of the same intensity, spot cones are ignored. Directional lights
+/// are not supported and are skipped.
+///
+/// The skipped orthographic cameras, directional lights, primitives and
+/// images, and the spot lights, are listed in the warnings of the import.
+pub fn load_gltf(path: &Path) -> Result<GltfImport, GltfError> {
+    zone!();
+    let (document, buffers, images) =
+        gltf::import(path).map_err(|err| GltfError::new(path, err.to_string()))?;
+    let Some(gltf_scene) = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+    else {
+        return Err(GltfError::new(path, "the file contains no scene"));
+    };
+
+    let mut warnings = Vec::new();
+    let textures = convert_images(path, &document, images, &mut warnings);
+    let materials: Vec<_> = document
+        .materials()
+        .map(|material| convert_material(&material, &textures))
+        .collect();
+
+    let mut importer = Importer {
+        path,
+        buffers: &buffers,
+        textures: &textures,
+        materials: &materials,
+        world: HittableWorld::new(),
+        bounds: None,
+        camera: None,
+        lights: Vec::new(),
+        warnings,
+    };
+    for node in gltf_scene.nodes() {
+        importer.add_node(&node, Mat4::IDENTITY)?;
+    }
+
+    let Importer {
+        mut world,
+        bounds,
+        camera,
+        lights,
+        warnings,
+        ..
+    } = importer;
+    let Some(bounds) = bounds else {
+        return Err(GltfError::new(path, "the scene contains no triangle"));
+    };
+
+    let center = (bounds.min() + bounds.max()) / 2.0;
+    let radius = (bounds.max() - bounds.min()).length() / 2.0;
+    let light_radius = (radius * LIGHT_RADIUS_RATIO).max(1e-3);
+    for (position, intensity) in &lights {
+        let radiance = *intensity * (1.0 / (PI * light_radius * light_radius));
+        world.add_sphere(Sphere::new(
+            *position,
+            light_radius,
+            Material::new_diffuse_light_color(radiance),
+        ));
+    }
+
+    let is_lit = !lights.is_empty()
+        || materials
+            .iter()
+            .any(|material| matches!(material, Material::DiffuseLight { .. }));
+    let background = if is_lit { Color::black() } else { SKY_COLOR };
+
+    let mut camera = camera.unwrap_or_else(|| {
+        let vertical_fov: f32 = 40.0;
+        let distance = radius / (vertical_fov.to_radians() / 2.0).tan();
+        Camera::new(
+            center + Vec3A::Z * (radius + distance),
+            center,
+            Vec3A::Y,
+            vertical_fov,
+            ASPECT_RATIO,
+            0.0,
+            distance,
+        )
+    });
+    camera.set_time(0.0, 1.0);
+
+    world.init_bvh_nodes();
+
+    Ok(GltfImport {
+        scene: Scene::new(world, camera, background),
+        warnings,
+    })
+}
+
+struct Importer<'a> {
+    path: &'a Path,
+    buffers: &'a [buffer::Data],
+    textures: &'a [Option<Texture>],
+    materials: &'a [Material],
+    world: HittableWorld,
+    bounds: Option<Aabb>,
+    camera: Option<Camera>,
+    lights: Vec<(Vec3A, Color)>,
+    warnings: Vec<String>,
+}
+
+impl<'a> Importer<'a> {
+    fn add_node(&mut self, node: &Node, parent_transform: Mat4) -> Result<(), GltfError> {
+        let transform = parent_transform * Mat4::from_cols_array_2d(&node.transform().matrix());
+
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                self.add_primitive(&primitive, transform)?;
+            }
+        }
+
+        if let Some(camera) = node.camera() {
+            match camera.projection() {
+                Projection::Perspective(perspective) if self.camera.is_none() => {
+                    let look_from = transform.transform_point3a(Vec3A::ZERO);
+                    let forward = transform.transform_vector3a(-Vec3A::Z).normalize();
+                    let vup = transform.transform_vector3a(Vec3A::Y).normalize();
+                    // The aspect ratio of the file is ignored like the one of
+                    // the built-in scenes, the image size giving it.
+                    self.camera = Some(Camera::new(
+                        look_from,
+                        look_from + forward,
+                        vup,
+                        perspective.yfov().to_degrees(),
+                        ASPECT_RATIO,
+                        0.0,
+                        1.0,
+                    ));
+                }
+                Projection::Perspective(_) => {}
+                Projection::Orthographic(_) => {
+                    self.warnings.push(format!(
+                        "Skipping the orthographic camera {}",
+                        camera.index()
+                    ));
+                }
+            }
+        }
+
+        if let Some(light) = node.light() {
+            let position = transform.transform_point3a(Vec3A::ZERO);
+            let [r, g, b] = light.color();
+            let intensity = Color::new(r, g, b) * light.intensity();
+            match light.kind() {
+                Kind::Point => self.lights.push((position, intensity)),
+                Kind::Spot { .. } => {
+                    self.warnings.push(format!(
+                        "Importing the spot light {} as a point light",
+                        light.index()
+                    ));
+                    self.lights.push((position, intensity));
+                }
+                Kind::Directional => {
+                    self.warnings
+                        .push(format!("Skipping the directional light {}", light.index()));
+                }
+            }
+        }
+
+        for child in node.children() {
+            self.add_node(&child, transform)?;
+        }
+
+        Ok(())
+    }
+
+    fn add_primitive(
+        &mut self,
+        primitive: &gltf::Primitive,
+        transform: Mat4,
+    ) -> Result<(), GltfError> {
+        let reader = primitive.reader(|buffer| Some(&self.buffers[buffer.index()]));
+        let Some(positions) = reader.read_positions() else {
+            return Err(GltfError::new(
+                self.path,
+                format!("a primitive of mesh {} has no positions", primitive.index()),
+            ));
+        };
+
+        let positions: Vec<_> = positions
+            .map(|position| transform.transform_point3a(Vec3A::from(position)))
+            .collect();
+        let normal_transform = Mat3A::from_mat4(transform).inverse().transpose();
+        let normals: Vec<_> = reader.read_normals().map_or_else(Vec::new, |normals| {
+            normals
+                .map(|normal| (normal_transform * Vec3A::from(normal)).normalize_or_zero())
+                .collect()
+        });
+        // glTF texture coordinates start at the top of the image.
+        let uvs: Vec<_> = reader.read_tex_coords(0).map_or_else(Vec::new, |uvs| {
+            uvs.into_f32().map(|[u, v]| Vec2::new(u, 1.0 - v)).collect()
+        });
+        let vertex_indices: Vec<u32> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..positions.len() as u32).collect(),
+        };
+
+        let mut indices: Vec<[u32; 3]> = match primitive.mode() {
+            Mode::Triangles => vertex_indices
+                .chunks_exact(3)
+                .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+                .collect(),
+            Mode::TriangleStrip => (0..vertex_indices.len().saturating_sub(2))
+                .map(|i| {
+                    let [a, b, c] = [
+                        vertex_indices[i],
+                        vertex_indices[i + 1],
+                        vertex_indices[i + 2],
+                    ];
+                    if i % 2 == 0 {
+                        [a, b, c]
+                    } else {
+                        [b, a, c]
+                    }
+                })
+                .collect(),
+            Mode::TriangleFan => (1..vertex_indices.len().saturating_sub(1))
+                .map(|i| [vertex_indices[0], vertex_indices[i], vertex_indices[i + 1]])
+                .collect(),
+            mode => {
+                self.warnings.push(format!(
+                    "Skipping a primitive of mesh {} drawn as {mode:?}",
+                    primitive.index()
+                ));
+                return Ok(());
+            }
+        };
+
+        if indices
+            .iter()
+            .flatten()
+            .any(|&i| i as usize >= positions.len())
+        {
+            return Err(GltfError::new(
+                self.path,
+                format!(
+                    "a primitive of mesh {} has an index out of its vertices",
+                    primitive.index()
+                ),
+            ));
+        }
+
+        // A mirroring transform reverses the winding of the triangles.
+        if transform.determinant() < 0.0 {
+            for triangle in &mut indices {
+                triangle.swap(1, 2);
+            }
+        }
+
+        let material = match primitive.material().index() {
+            Some(index) => self.materials[index].clone(),
+            None => convert_material(&primitive.material(), self.textures),
+        };
+
+        let mesh = TriangleMesh::new(positions, normals, uvs, indices, material);
+        let mesh_bounds = (0..mesh.triangle_count())
+            .map(|triangle| mesh.triangle_bounding_box(triangle))
+            .reduce(Aabb::surrounding_box);
+        self.bounds = Aabb::opt_surrounding_box(self.bounds.take(), mesh_bounds);
+        self.world.add_triangle_mesh(mesh);
+
+        Ok(())
+    }
+}
+
+fn convert_material(material: &gltf::Material, textures: &[Option<Texture>]) -> Material {
+    let texture = |info: Option<gltf::texture::Info>| {
+        info.and_then(|info| textures[info.texture().source().index()].clone())
+    };
+
+    let [r, g, b] = material.emissive_factor();
+    let emission = Color::new(r, g, b) * material.emissive_strength().unwrap_or(1.0);
+    if emission.x.max(emission.y).max(emission.z) > 0.0 {
+        return match texture(material.emissive_texture()) {
+            Some(emit) => Material::new_diffuse_light(emit),
+            None => Material::new_diffuse_light_color(emission),
+        };
+    }
+
+    let pbr = material.pbr_metallic_roughness();
+    let [r, g, b, alpha] = pbr.base_color_factor();
+    let base_color = Color::new(r, g, b);
+    let transmission = material
+        .transmission()
+        .map_or(0.0, |transmission| transmission.transmission_factor());
+    if transmission >= 0.5 || (material.alpha_mode() == AlphaMode::Blend && alpha < 0.5) {
+        return Material::new_dielectric(material.ior().unwrap_or(1.5));
+    }
+
+    if pbr.metallic_factor() >= 0.5 {
+        return Material::new_metal(base_color, pbr.roughness_factor());
+    }
+
+    match texture(pbr.base_color_texture()) {
+        Some(albedo) => Material::new_lambertian(albedo),
+        None => Material::new_lambertian_color(base_color),
+    }
+}
+
+/// Converts the decoded images to RGB textures, named after their file or,
+/// when embedded, after the glTF file and their index.
+fn convert_images(
+    path: &Path,
+    document: &Document,
+    images: Vec<image::Data>,
+    warnings: &mut Vec<String>,
+) -> Vec<Option<Texture>> {
+    let directory = path.parent().unwrap_or(Path::new(""));
+
+    document
+        .images()
+        .zip(images)
+        .map(|(image, data)| {
+            let name = match image.source() {
+                Source::Uri { uri, .. } if !uri.starts_with("data:") => {
+                    directory.join(uri).display().to_string()
+                }
+                _ => format!("{}#image{}", path.display(), image.index()),
+            };
+
+            let Some(rgb) = to_rgb8(&data) else {
+                warnings.push(format!(
+                    "Skipping the image {name} of format {:?}",
+                    data.format
+                ));
+                return None;
+            };
+
+            Some(Texture::new_image_data(
+                name,
+                rgb,
+                data.width as usize,
+                data.height as usize,
+            ))
+        })
+        .collect()
+}
+
+fn to_rgb8(data: &image::Data) -> Option<Vec<u8>> {
+    // 16 bits channels are little endian, their most significant byte is the second.
+    let (channels, channel_size) = match data.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT | Format::R32G32B32A32FLOAT => return None,
+    };
+
+    let rgb = data
+        .pixels
+        .chunks_exact(channels * channel_size)
+        .flat_map(|pixel| {
+            let channel = |i: usize| pixel[i * channel_size + channel_size - 1];
+            if channels < 3 {
+                [channel(0); 3]
+            } else {
+                [channel(0), channel(1), channel(2)]
+            }
+        })
+        .collect();
+
+    Some(rgb)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::consts::ASPECT_RATIO;
+    use crate::import::gltf::load_gltf;
+    use glam::Vec3A;
+
+    const GLTF: &str = r#"{
+        "asset": { "version": "2.0" },
+        "extensionsUsed": ["KHR_lights_punctual"],
+        "extensions": {
+            "KHR_lights_punctual": {
+                "lights": [{ "type": "point", "color": [1, 1, 1], "intensity": 2 }]
+            }
+        },
+        "scene": 0,
+        "scenes": [{ "nodes": [0, 2, 3, 4] }],
+        "nodes": [
+            { "translation": [0, 0, -5], "children": [1] },
+            { "mesh": 0, "scale": [2, 2, 2] },
+            { "camera": 0, "translation": [0, 1, 0] },
+            { "translation": [0, 3, 0], "extensions": { "KHR_lights_punctual": { "light": 0 } } },
+            { "camera": 1 }
+        ],
+        "cameras": [
+            { "type": "perspective", "perspective": { "yfov": 0.5, "aspectRatio": 2, "znear": 0.1 } },
+            { "type": "orthographic", "orthographic": { "xmag": 1, "ymag": 1, "zfar": 10, "znear": 0 } }
+        ],
+        "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "material": 0 }] }],
+        "materials": [{ "pbrMetallicRoughness": { "baseColorFactor": [1, 0, 0, 1], "metallicFactor": 0 } }],
+        "accessors": [{
+            "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+            "min": [0, 0, 0], "max": [1, 1, 0]
+        }],
+        "bufferViews": [{ "buffer": 0, "byteLength": 36 }],
+        "buffers": [{ "uri": "triangle.bin", "byteLength": 36 }]
+    }"#;
+
+    #[test]
+    fn gltf_nodes_are_transformed() {
+        let directory = std::env::temp_dir().join("raytracing_gltf_nodes");
+        std::fs::create_dir_all(&directory).unwrap();
+        let positions: [f32; 9] = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+        let bytes: Vec<u8> = positions.iter().flat_map(|p| p.to_le_bytes()).collect();
+        std::fs::write(directory.join("triangle.bin"), bytes).unwrap();
+        std::fs::write(directory.join("scene.gltf"), GLTF).unwrap();
+
+        let import = load_gltf(&directory.join("scene.gltf")).unwrap();
+        let scene = import.scene;
+
+        let mesh = &scene.hittable_list().triangle_meshes()[0];
+        assert_eq!(
+            mesh.positions(),
+            [
+                Vec3A::new(0.0, 0.0, -5.0),
+                Vec3A::new(2.0, 0.0, -5.0),
+                Vec3A::new(0.0, 2.0, -5.0)
+            ]
+        );
+        assert_eq!(scene.camera().look_from(), Vec3A::Y);
+        assert!((scene.camera().vertical_fov() - 0.5f32.to_degrees()).abs() < 1e-4);
+        assert_eq!(scene.camera().aspect_ratio(), ASPECT_RATIO);
+        assert_eq!(
+            scene.hittable_list().spheres()[0].center(),
+            Vec3A::new(0.0, 3.0, 0.0)
+        );
+        assert_eq!(import.warnings, ["Skipping the orthographic camera 1"]);
+    }
+}
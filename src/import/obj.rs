@@ -0,0 +1,471 @@
+use crate::geometry::triangle_mesh::TriangleMesh;
+use crate::material::Material;
+use crate::math::color::Color;
+use crate::texture::Texture;
+use glam::{Vec2, Vec3A};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::SplitWhitespace;
+use tracy_full::zone;
+
+/// Material of faces that are not preceded by any `usemtl`.
+const DEFAULT_COLOR: Color = Color::new(0.73, 0.73, 0.73);
+
+/// Error produced while importing an OBJ or MTL file.
+#[derive(Debug)]
+pub struct ObjError {
+    path: PathBuf,
+    line: Option<usize>,
+    message: String,
+}
+
+impl ObjError {
+    fn new(path: &Path, line: Option<usize>, message: impl Into<String>) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            line,
+            message: message.into(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Line of the file the error refers to, starting at 1.
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", self.path.display())?;
+        if let Some(line) = self.line {
+            write!(f, "{line}:")?;
+        }
+
+        write!(f, " {}", self.message)
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+/// Triangles of an OBJ group that share a material.
+pub struct ObjMesh {
+    pub name: String,
+    pub mesh: TriangleMesh,
+}
+
+/// Imports the faces of a Wavefront OBJ file as triangle meshes.
+///
+/// A mesh is created for every group (`g` or `o`) and material (`usemtl`)
+/// combination. Polygons are triangulated as fans, so they must be convex.
+/// The materials of the `mtllib` files are mapped on [`Material`]:
+///
+/// * `Ke` makes a [`Material::DiffuseLight`].
+/// * `d` below 1 (or `Tr` above 0) makes a [`Material::Dielectric`] of index `Ni`.
+/// * `Ks` brighter than `Kd` makes a [`Material::Metal`], `Ns` sets its fuzz.
+/// * Otherwise `Kd`, or the PNG image of `map_Kd`, makes a [`Material::Lambertian`].
+pub fn load_obj(path: &Path) -> Result<Vec<ObjMesh>, ObjError> {
+    zone!();
+    let source = std::fs::read_to_string(path)
+        .map_err(|err| ObjError::new(path, None, format!("could not read file: {err}")))?;
+    let directory = path.parent().unwrap_or(Path::new(""));
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut materials: HashMap<String, Material> = HashMap::new();
+    let mut meshes = Vec::new();
+    let mut builder = MeshBuilder::new(
+        "default".to_string(),
+        Material::new_lambertian_color(DEFAULT_COLOR),
+    );
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = Some(index + 1);
+        let error = |message: String| ObjError::new(path, line_number, message);
+        let mut tokens = strip_comment(line).split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+
+        match keyword {
+            "v" => positions.push(parse_vec3(&mut tokens).map_err(error)?),
+            "vn" => normals.push(parse_vec3(&mut tokens).map_err(error)?),
+            "vt" => uvs.push(parse_uv(&mut tokens).map_err(error)?),
+            "f" => builder
+                .add_face(tokens, &positions, &normals, &uvs)
+                .map_err(error)?,
+            "g" | "o" => {
+                let name = tokens.collect::<Vec<_>>().join(" ");
+                let material = builder.material.clone();
+                builder.finish_into(&mut meshes);
+                builder = MeshBuilder::new(name, material);
+            }
+            "usemtl" => {
+                let name = tokens.collect::<Vec<_>>().join(" ");
+                let Some(material) = materials.get(&name) else {
+                    return Err(error(format!("unknown material `{name}`")));
+                };
+
+                let group = builder.name.clone();
+                builder.finish_into(&mut meshes);
+                builder = MeshBuilder::new(group, material.clone());
+            }
+            "mtllib" => {
+                for file in tokens {
+                    materials.extend(load_mtl(&directory.join(file))?);
+                }
+            }
+            _ => {}
+        }
+    }
+    builder.finish_into(&mut meshes);
+
+    Ok(meshes)
+}
+
+/// Reads the materials of a MTL file.
+fn load_mtl(path: &Path) -> Result<HashMap<String, Material>, ObjError> {
+    let source = std::fs::read_to_string(path)
+        .map_err(|err| ObjError::new(path, None, format!("could not read file: {err}")))?;
+    let directory = path.parent().unwrap_or(Path::new(""));
+
+    let mut materials = HashMap::new();
+    let mut current: Option<MtlMaterial> = None;
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let error = |message: String| ObjError::new(path, Some(line_number), message);
+        let mut tokens = strip_comment(line).split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+
+        if keyword == "newmtl" {
+            if let Some(material) = current.take() {
+                materials.insert(material.name.clone(), material.to_material(path)?);
+            }
+
+            current = Some(MtlMaterial::new(tokens.collect::<Vec<_>>().join(" ")));
+            continue;
+        }
+
+        let Some(material) = current.as_mut() else {
+            if matches!(
+                keyword,
+                "Kd" | "Ks" | "Ke" | "Ns" | "Ni" | "d" | "Tr" | "map_Kd"
+            ) {
+                return Err(error(format!("`{keyword}` found before any `newmtl`")));
+            }
+            continue;
+        };
+
+        match keyword {
+            "Kd" => material.diffuse = parse_color(&mut tokens).map_err(error)?,
+            "Ks" => material.specular = parse_color(&mut tokens).map_err(error)?,
+            "Ke" => material.emission = parse_color(&mut tokens).map_err(error)?,
+            "Ns" => material.shininess = parse_float(tokens.next()).map_err(error)?,
+            "Ni" => material.refraction_index = Some(parse_float(tokens.next()).map_err(error)?),
+            "d" => material.dissolve = parse_float(tokens.next()).map_err(error)?,
+            "Tr" => material.dissolve = 1.0 - parse_float(tokens.next()).map_err(error)?,
+            "map_Kd" => {
+                // Texture options come before the file name.
+                let Some(file) = tokens.last() else {
+                    return Err(error("missing texture file name".to_string()));
+                };
+
+                material.diffuse_map = Some((directory.join(file), line_number));
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(material) = current {
+        materials.insert(material.name.clone(), material.to_material(path)?);
+    }
+
+    Ok(materials)
+}
+
+struct MtlMaterial {
+    name: String,
+    diffuse: Color,
+    diffuse_map: Option<(PathBuf, usize)>,
+    specular: Color,
+    shininess: f32,
+    emission: Color,
+    refraction_index: Option<f32>,
+    dissolve: f32,
+}
+
+impl MtlMaterial {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            diffuse: DEFAULT_COLOR,
+            diffuse_map: None,
+            specular: Color::black(),
+            shininess: 0.0,
+            emission: Color::black(),
+            refraction_index: None,
+            dissolve: 1.0,
+        }
+    }
+
+    fn to_material(&self, path: &Path) -> Result<Material, ObjError> {
+        if max_component(&self.emission) > 0.0 {
+            return Ok(Material::new_diffuse_light_color(self.emission));
+        }
+
+        if self.dissolve < 1.0 {
+            let refraction_index = self.refraction_index.filter(|&index| index > 1.0);
+            return Ok(Material::new_dielectric(refraction_index.unwrap_or(1.5)));
+        }
+
+        if max_component(&self.specular) > max_component(&self.diffuse) {
+            // Phong exponent to a roughness, as proposed by Walter et al.
+            let fuzz = (2.0 / (self.shininess.max(0.0) + 2.0)).sqrt();
+            return Ok(Material::new_metal(self.specular, fuzz));
+        }
+
+        match &self.diffuse_map {
+            Some((file, line)) => {
+                let texture =
+                    Texture::new_image(file.to_string_lossy().into_owned()).ok_or_else(|| {
+                        ObjError::new(
+                            path,
+                            Some(*line),
+                            format!("could not load texture `{}`", file.display()),
+                        )
+                    })?;
+
+                Ok(Material::new_lambertian(texture))
+            }
+            None => Ok(Material::new_lambertian_color(self.diffuse)),
+        }
+    }
+}
+
+/// Gathers the faces of a group until its material or group changes.
+struct MeshBuilder {
+    name: String,
+    material: Material,
+    vertices: HashMap<(usize, Option<usize>, Option<usize>), u32>,
+    positions: Vec<Vec3A>,
+    normals: Vec<Option<Vec3A>>,
+    uvs: Vec<Option<Vec2>>,
+    indices: Vec<[u32; 3]>,
+}
+
+impl MeshBuilder {
+    fn new(name: String, material: Material) -> Self {
+        Self {
+            name,
+            material,
+            vertices: HashMap::new(),
+            positions: Vec::new(),
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            indices: Vec::new(),
+        }
+    }
+
+    fn add_face(
+        &mut self,
+        tokens: SplitWhitespace,
+        positions: &[Vec3A],
+        normals: &[Vec3A],
+        uvs: &[Vec2],
+    ) -> Result<(), String> {
+        let mut face = Vec::new();
+        for token in tokens {
+            let mut parts = token.split('/');
+            let position = resolve_index(parts.next(), positions.len(), "position")?
+                .ok_or_else(|| format!("face vertex `{token}` has no position"))?;
+            let uv = resolve_index(parts.next(), uvs.len(), "texture coordinate")?;
+            let normal = resolve_index(parts.next(), normals.len(), "normal")?;
+
+            let key = (position, uv, normal);
+            let index = match self.vertices.get(&key) {
+                Some(&index) => index,
+                None => {
+                    let index = self.positions.len() as u32;
+                    self.positions.push(positions[position]);
+                    self.uvs.push(uv.map(|uv| uvs[uv]));
+                    self.normals.push(normal.map(|normal| normals[normal]));
+                    self.vertices.insert(key, index);
+                    index
+                }
+            };
+            face.push(index);
+        }
+
+        if face.len() < 3 {
+            return Err(format!("a face needs 3 vertices, found {}", face.len()));
+        }
+
+        for i in 1..face.len() - 1 {
+            self.indices.push([face[0], face[i], face[i + 1]]);
+        }
+
+        Ok(())
+    }
+
+    fn finish_into(self, meshes: &mut Vec<ObjMesh>) {
+        if self.indices.is_empty() {
+            return;
+        }
+
+        // A mesh has an attribute on every vertex or on none of them.
+        let normals = self.normals.into_iter().collect::<Option<Vec<_>>>();
+        let uvs = self.uvs.into_iter().collect::<Option<Vec<_>>>();
+
+        meshes.push(ObjMesh {
+            name: self.name,
+            mesh: TriangleMesh::new(
+                self.positions,
+                normals.unwrap_or_default(),
+                uvs.unwrap_or_default(),
+                self.indices,
+                self.material,
+            ),
+        });
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    line.split('#').next().unwrap_or_default()
+}
+
+/// Converts a 1-based, or negative relative, OBJ index to a 0-based index.
+fn resolve_index(token: Option<&str>, len: usize, kind: &str) -> Result<Option<usize>, String> {
+    let Some(token) = token.filter(|token| !token.is_empty()) else {
+        return Ok(None);
+    };
+
+    let index: i64 = token
+        .parse()
+        .map_err(|_| format!("invalid {kind} index `{token}`"))?;
+    let resolved = if index < 0 {
+        len as i64 + index
+    } else {
+        index - 1
+    };
+
+    if index == 0 || resolved < 0 || resolved >= len as i64 {
+        return Err(format!("{kind} index {index} is out of the {len} defined"));
+    }
+
+    Ok(Some(resolved as usize))
+}
+
+fn parse_float(token: Option<&str>) -> Result<f32, String> {
+    let token = token.ok_or_else(|| "missing number".to_string())?;
+    token
+        .parse()
+        .map_err(|_| format!("invalid number `{token}`"))
+}
+
+fn parse_vec3(tokens: &mut SplitWhitespace) -> Result<Vec3A, String> {
+    Ok(Vec3A::new(
+        parse_float(tokens.next())?,
+        parse_float(tokens.next())?,
+        parse_float(tokens.next())?,
+    ))
+}
+
+fn parse_uv(tokens: &mut SplitWhitespace) -> Result<Vec2, String> {
+    let u = parse_float(tokens.next())?;
+    let v = tokens.next().map_or(Ok(0.0), |v| parse_float(Some(v)))?;
+
+    Ok(Vec2::new(u, v))
+}
+
+fn parse_color(tokens: &mut SplitWhitespace) -> Result<Color, String> {
+    let r = parse_float(tokens.next())?;
+    // A single value is a grey.
+    let Some(g) = tokens.next() else {
+        return Ok(Color::new(r, r, r));
+    };
+
+    Ok(Color::new(
+        r,
+        parse_float(Some(g))?,
+        parse_float(tokens.next())?,
+    ))
+}
+
+fn max_component(color: &Color) -> f32 {
+    color.x.max(color.y).max(color.z)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::import::obj::load_obj;
+    use crate::material::Material;
+    use std::path::PathBuf;
+
+    fn write_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("raytracing_obj_{name}"));
+        std::fs::create_dir_all(&directory).unwrap();
+        for (file, content) in files {
+            std::fs::write(directory.join(file), content).unwrap();
+        }
+
+        directory.join(files[0].0)
+    }
+
+    #[test]
+    fn obj_groups_and_materials_make_meshes() {
+        let path = write_files(
+            "groups",
+            &[
+                (
+                    "model.obj",
+                    "mtllib model.mtl\n\
+                     v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+                     vn 0 0 1\n\
+                     g quad\nusemtl chrome\nf 1//1 2//1 3//1 4//1\n\
+                     g light\nusemtl lamp\nf -4 -3 -2\n",
+                ),
+                (
+                    "model.mtl",
+                    "newmtl chrome\nKd 0.1 0.1 0.1\nKs 0.9 0.9 0.9\nNs 1000\n\
+                     newmtl lamp\nKe 4 4 4\n",
+                ),
+            ],
+        );
+
+        let meshes = load_obj(&path).unwrap();
+
+        assert_eq!(meshes.len(), 2);
+        assert_eq!(meshes[0].name, "quad");
+        assert_eq!(meshes[0].mesh.triangle_count(), 2);
+        assert_eq!(meshes[0].mesh.normals().len(), 4);
+        assert!(matches!(meshes[0].mesh.material(), Material::Metal { .. }));
+        assert_eq!(meshes[1].name, "light");
+        assert!(meshes[1].mesh.normals().is_empty());
+        assert!(matches!(
+            meshes[1].mesh.material(),
+            Material::DiffuseLight { .. }
+        ));
+    }
+
+    #[test]
+    fn obj_errors_report_file_and_line() {
+        let path = write_files("errors", &[("broken.obj", "v 0 0 0\nv 1 0 0\nf 1 2 3\n")]);
+
+        let error = load_obj(&path).err().unwrap();
+
+        assert_eq!(error.path(), path);
+        assert_eq!(error.line(), Some(3));
+    }
+}
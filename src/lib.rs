@@ -1,53 +1,293 @@
+pub mod aov;
 pub mod camera;
+pub mod cancellation;
+pub mod checkpoint;
+pub mod cli;
 pub mod consts;
+pub mod denoise;
+pub mod distributed;
+pub mod film;
+pub mod framebuffer;
 pub mod geometry;
+pub mod import;
+pub mod light;
 pub mod material;
 pub mod math;
+pub mod output;
 pub mod ray;
 pub mod renderer;
+pub mod sampler;
 pub mod scene;
+pub mod scene_description;
 pub mod texture;
+pub mod tile;
+pub mod tone_mapping;
 
-use consts::*;
+use clap::Parser;
 use human_time::ToHumanTimeString;
-use std::io::BufWriter;
-use std::path::Path;
-use std::{fs::File, time::Instant};
 use rand_xoshiro::rand_core::SeedableRng;
+use std::net::TcpListener;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
 
-use crate::renderer::render;
-use crate::scene::Scene;
+use crate::cancellation::CancellationToken;
+use crate::checkpoint::CheckpointError;
+use crate::cli::Cli;
+use crate::denoise::denoise;
+use crate::distributed::{render_distributed, run_worker, DistributedError};
+use crate::framebuffer::Framebuffer;
+use crate::geometry::bvh::BvhBuilder;
+use crate::import::gltf::load_gltf;
+use crate::output::{
+    denoised_path, sample_heatmap_path, write_image, write_png, ImageFormat, OutputSettings,
+};
+use crate::renderer::{render, render_cancellable, ProgressiveRenderer, RenderSettings};
+use crate::scene::{Scene, BUILT_IN_SCENES};
 
-const FILE_DEFAULT_PATH: &str = "out.png";
+pub fn run() -> ExitCode {
+    let cli = Cli::parse();
 
-pub fn run() {
+    if cli.list_scenes {
+        for name in BUILT_IN_SCENES {
+            println!("{name}");
+        }
+        return ExitCode::SUCCESS;
+    }
+
+    if let Some(threads) = cli.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads as usize)
+            .build_global()
+            .expect("The global thread pool should not be initialized yet");
+    }
+
+    if let Some(address) = &cli.worker {
+        println!("Working for {address}");
+        return match run_worker(address) {
+            Ok(()) => {
+                println!("Render finished");
+                ExitCode::SUCCESS
+            }
+            Err(err) => {
+                eprintln!("Could not work for {address} : {err}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    if let Err(err) = ImageFormat::from_path(&cli.output) {
+        eprintln!("{err}");
+        return ExitCode::FAILURE;
+    }
+
+    let mut scene = match load_scene(&cli) {
+        Ok(scene) => scene,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if let Some(path) = &cli.export_scene {
+        let result = scene.to_description().and_then(|scene| scene.save(path));
+        if let Err(err) = result {
+            eprintln!("Could not export the scene : {err}");
+            return ExitCode::FAILURE;
+        }
+
+        println!("Scene written to {}", path.display());
+        return ExitCode::SUCCESS;
+    }
+
+    let builder = cli.bvh_builder();
+    if builder != BvhBuilder::RandomMedian {
+        scene.build_bvh(builder);
+    }
+
+    if cli.bvh_stats {
+        println!("BVH: {}", scene.hittable_list().bvh_stats());
+    }
+
+    let settings = cli.render_settings();
+    let skipped_spheres = scene.lights().skipped_spheres();
+    if settings.sample_lights && skipped_spheres > 0 {
+        eprintln!(
+            "{skipped_spheres} light spheres in non uniformly scaled instances are only found by bouncing"
+        );
+    }
     let start = Instant::now();
+    let cancellation = match cli.time_limit {
+        Some(limit) => CancellationToken::new().with_time_budget(limit),
+        None => CancellationToken::new(),
+    };
 
-    let mut rng = rand_xoshiro::Xoshiro256Plus::seed_from_u64(0);
-    let scene = Scene::perlin_and_earth(&mut rng);
-    let pixels = render(&scene, IMAGE_WIDTH, IMAGE_HEIGHT);
+    let output_settings = cli.output_settings();
+    let mut framebuffer = if let Some(address) = &cli.listen {
+        match render_on_workers(address, &scene, &settings) {
+            Ok(framebuffer) => framebuffer,
+            Err(err) => {
+                eprintln!("Could not render on the workers : {err}");
+                return ExitCode::FAILURE;
+            }
+        }
+    } else if cli.progressive.is_some() || cli.checkpoint.is_some() {
+        match render_in_passes(&cli, &scene, &settings, &output_settings, &cancellation) {
+            Ok(framebuffer) => framebuffer,
+            Err(err) => {
+                eprintln!("Could not resume or checkpoint the render : {err}");
+                return ExitCode::FAILURE;
+            }
+        }
+    } else if cli.time_limit.is_some() {
+        render_cancellable(&scene, &settings, &cancellation)
+    } else {
+        render(&scene, &settings)
+    };
 
     println!(
         "Raytracing finished in {}",
         start.elapsed().to_human_time_string()
     );
+    if cancellation.is_cancelled() {
+        println!("Render stopped by the time limit");
+    }
+    let partial = settings.adaptive_sampling.is_some() || cancellation.is_cancelled();
+    if let (true, Some(counts)) = (partial, framebuffer.sample_counts()) {
+        let total: u64 = counts.iter().map(|&count| count as u64).sum();
+        println!(
+            "Mean samples per pixel: {:.1}",
+            total as f64 / counts.len() as f64
+        );
+    }
 
-    write_image(&pixels, Path::new(FILE_DEFAULT_PATH));
-}
+    let denoised = cli.denoise.then(|| {
+        let start = Instant::now();
+        let denoised = denoise(&framebuffer, &cli.denoise_settings());
+        println!(
+            "Denoising finished in {}",
+            start.elapsed().to_human_time_string()
+        );
+        denoised
+    });
+    // The AOVs guiding the denoiser are only written when requested.
+    if !cli.aovs {
+        framebuffer.take_aovs();
+    }
 
-fn write_image(pixels: &[u8], path: &Path) {
     println!("Writing image...");
+    if let Err(err) = write_image(&framebuffer, &cli.output, &output_settings) {
+        eprintln!("Could not write the image : {err}");
+        return ExitCode::FAILURE;
+    }
+    println!("Image written to {}", cli.output.display());
 
-    let file = File::create(path).unwrap();
-    let w = &mut BufWriter::new(file);
+    if let Some(denoised) = denoised {
+        let path = denoised_path(&cli.output);
+        if let Err(err) = write_image(&denoised, &path, &output_settings) {
+            eprintln!("Could not write the denoised image : {err}");
+            return ExitCode::FAILURE;
+        }
+        println!("Denoised image written to {}", path.display());
+    }
 
-    let mut encoder = png::Encoder::new(w, IMAGE_WIDTH as u32, IMAGE_HEIGHT as u32);
-    encoder.set_color(png::ColorType::Rgb);
-    encoder.set_depth(png::BitDepth::Eight);
+    if cli.sample_heatmap {
+        let path = sample_heatmap_path(&cli.output);
+        let heatmap = framebuffer
+            .sample_heatmap()
+            .expect("The renderer should count the samples of the pixels");
+        if let Err(err) = write_png(&heatmap, &path, &OutputSettings::default()) {
+            eprintln!("Could not write the sample heatmap : {err}");
+            return ExitCode::FAILURE;
+        }
+        println!("Sample heatmap written to {}", path.display());
+    }
+
+    ExitCode::SUCCESS
+}
+
+fn render_on_workers(
+    address: &str,
+    scene: &Scene,
+    settings: &RenderSettings,
+) -> Result<Framebuffer, DistributedError> {
+    let listener = TcpListener::bind(address)?;
+    println!("Waiting for workers on {}", listener.local_addr()?);
+
+    render_distributed(scene, settings, &listener)
+}
+
+/// Renders in passes of `--progressive` samples, or of one sample when only
+/// checkpointing, writing the image and the checkpoint between them.
+///
+/// The first checkpoint is written after the first pass, so that the scenes
+/// which can not be checkpointed are refused early. A checkpoint is also
+/// written when the render is cancelled, so that it can be resumed.
+fn render_in_passes(
+    cli: &Cli,
+    scene: &Scene,
+    settings: &RenderSettings,
+    output_settings: &OutputSettings,
+    cancellation: &CancellationToken,
+) -> Result<Framebuffer, CheckpointError> {
+    let renderer = match &cli.checkpoint {
+        Some(path) if cli.resume => {
+            let renderer = ProgressiveRenderer::resume(scene, settings, path)?;
+            println!("Render resumed from {}", path.display());
+            renderer
+        }
+        _ => ProgressiveRenderer::new(scene, settings),
+    };
+    let mut renderer = renderer.with_cancellation(cancellation.clone());
+
+    let checkpoint_interval = Duration::from_secs(cli.checkpoint_interval);
+    let mut last_checkpoint: Option<Instant> = None;
+    let mut pass = 0;
+    while !renderer.is_finished() && !cancellation.is_cancelled() {
+        pass += 1;
+        renderer.render_pass(cli.progressive.unwrap_or(1));
+
+        if cli.progressive.is_some() {
+            let mut snapshot = renderer.framebuffer();
+            snapshot.take_aovs();
+            match write_image(&snapshot, &cli.output, output_settings) {
+                Ok(()) => println!("Pass {pass} written to {}", cli.output.display()),
+                Err(err) => eprintln!("Could not write the image of pass {pass} : {err}"),
+            }
+        }
+        if let Some(path) = &cli.checkpoint {
+            let stopped = renderer.is_finished() || cancellation.is_cancelled();
+            let due = last_checkpoint.is_none_or(|last| last.elapsed() >= checkpoint_interval);
+            if stopped || due {
+                renderer.save_checkpoint(path)?;
+                println!("Checkpoint written to {}", path.display());
+                last_checkpoint = Some(Instant::now());
+            }
+        }
+    }
+
+    Ok(renderer.framebuffer())
+}
 
-    let mut writer = encoder.write_header().unwrap();
-    writer.write_image_data(pixels).unwrap();
+fn load_scene(cli: &Cli) -> Result<Scene, String> {
+    if let Some(path) = &cli.scene_file {
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        return match extension {
+            Some("gltf" | "glb") => {
+                let import = load_gltf(path).map_err(|err| err.to_string())?;
+                for warning in &import.warnings {
+                    eprintln!("{warning}");
+                }
+                Ok(import.scene)
+            }
+            _ => Scene::from_file(path).map_err(|err| err.to_string()),
+        };
+    }
 
-    let path_str = path.to_str().unwrap();
-    println!("Image written to {path_str}");
+    let mut rng = rand_xoshiro::Xoshiro256Plus::seed_from_u64(cli.seed);
+    Scene::built_in(&cli.scene, &mut rng).ok_or_else(|| {
+        format!(
+            "Unknown scene `{}`, use --list-scenes to see the available scenes",
+            cli.scene
+        )
+    })
 }
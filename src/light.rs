@@ -0,0 +1,540 @@
+use crate::geometry::hit::HittableObjectType;
+use crate::geometry::hittable_world::{HittableObjectIndex, HittableWorld};
+use crate::material::Material;
+use crate::ray::Ray;
+use glam::{Affine3A, Mat3A, Vec3A};
+use rand::Rng;
+use rand_xoshiro::rand_core::RngCore;
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::ops::Range;
+use tracy_full::zone;
+
+/// Shape of an emissive primitive of the world, in world space, that can be
+/// sampled to light the surfaces directly.
+///
+/// The emission itself is not stored, it is read from the material of the
+/// surface hit by the shadow ray towards the sampled point.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Light {
+    Sphere {
+        center: Vec3A,
+        radius: f32,
+    },
+    MovingSphere {
+        center0: Vec3A,
+        center1: Vec3A,
+        time0: f32,
+        time1: f32,
+        radius: f32,
+    },
+    /// Rectangles and the faces of boxes, possibly transformed by instances.
+    Parallelogram {
+        corner: Vec3A,
+        edge_u: Vec3A,
+        edge_v: Vec3A,
+    },
+    Triangle {
+        vertices: [Vec3A; 3],
+    },
+}
+
+/// Point sampled on a light from a shaded point.
+#[derive(Debug, Clone, Copy)]
+pub struct LightSample {
+    pub point: Vec3A,
+    /// Density of the direction towards `point`, with respect to the solid
+    /// angle seen from the shaded point.
+    pub pdf: f32,
+}
+
+/// Lights of a world, with the ones of each of its objects.
+#[derive(Debug, Clone, Default)]
+pub struct Lights {
+    lights: Vec<Light>,
+    /// Lights of the emissive objects, by [`HittableWorld::object_id`], the
+    /// lights of an instance being all the ones of its object.
+    object_lights: HashMap<usize, Range<usize>>,
+    skipped_spheres: usize,
+}
+
+impl Lights {
+    /// Collects the primitives of `world` that have a
+    /// [`Material::DiffuseLight`], including the ones of its instances.
+    ///
+    /// Spheres in instances that do not scale uniformly are not collected,
+    /// their emission is then only found by bouncing, see
+    /// [`Lights::skipped_spheres`].
+    pub fn new(world: &HittableWorld) -> Self {
+        let mut lights = Self::default();
+        collect_lights(world, &Affine3A::IDENTITY, None, &mut lights);
+
+        lights
+    }
+
+    pub fn as_slice(&self) -> &[Light] {
+        &self.lights
+    }
+
+    pub fn len(&self) -> usize {
+        self.lights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    /// Gets the lights of the object with the id `object_id` in the world.
+    pub fn of_object(&self, object_id: usize) -> &[Light] {
+        self.object_lights
+            .get(&object_id)
+            .map_or(&[], |range| &self.lights[range.clone()])
+    }
+
+    /// Number of light spheres which were not collected, being in instances
+    /// that do not scale uniformly.
+    pub fn skipped_spheres(&self) -> usize {
+        self.skipped_spheres
+    }
+
+    fn push(&mut self, object_id: usize, light: Light) {
+        let index = self.lights.len();
+        self.lights.push(light);
+        self.object_lights
+            .entry(object_id)
+            .or_insert(index..index)
+            .end = index + 1;
+    }
+}
+
+impl Light {
+    /// Samples a point of the light visible from `origin`, or nothing if the
+    /// light can not be seen from there.
+    pub fn sample(&self, origin: Vec3A, time: f32, rng: &mut impl RngCore) -> Option<LightSample> {
+        zone!();
+        match self {
+            Light::Sphere { center, radius } => sample_sphere(*center, *radius, origin, rng),
+            Light::MovingSphere {
+                center0,
+                center1,
+                time0,
+                time1,
+                radius,
+            } => {
+                let center = *center0 + ((time - time0) / (time1 - time0)) * (*center1 - *center0);
+                sample_sphere(center, *radius, origin, rng)
+            }
+            Light::Parallelogram {
+                corner,
+                edge_u,
+                edge_v,
+            } => {
+                let point = *corner + rng.gen::<f32>() * *edge_u + rng.gen::<f32>() * *edge_v;
+                area_sample(origin, point, edge_u.cross(*edge_v))
+            }
+            Light::Triangle { vertices } => {
+                let (mut a, mut b) = (rng.gen::<f32>(), rng.gen::<f32>());
+                if a + b > 1.0 {
+                    a = 1.0 - a;
+                    b = 1.0 - b;
+                }
+                let edge_u = vertices[1] - vertices[0];
+                let edge_v = vertices[2] - vertices[0];
+                let point = vertices[0] + a * edge_u + b * edge_v;
+                area_sample(origin, point, 0.5 * edge_u.cross(edge_v))
+            }
+        }
+    }
+
+    /// Finds the point at which `ray` first reaches the light, with the
+    /// density with which [`Light::sample`] chooses its direction from the
+    /// origin of the ray.
+    pub fn pdf(&self, ray: &Ray) -> Option<LightSample> {
+        zone!();
+        let origin = ray.origin();
+        match self {
+            Light::Sphere { center, radius } => sphere_pdf(*center, *radius, ray),
+            Light::MovingSphere {
+                center0,
+                center1,
+                time0,
+                time1,
+                radius,
+            } => {
+                let center =
+                    *center0 + ((ray.time - time0) / (time1 - time0)) * (*center1 - *center0);
+                sphere_pdf(center, *radius, ray)
+            }
+            Light::Parallelogram {
+                corner,
+                edge_u,
+                edge_v,
+            } => {
+                let (point, a, b) = hit_plane(*corner, *edge_u, *edge_v, ray)?;
+                if !(0.0..=1.0).contains(&a) || !(0.0..=1.0).contains(&b) {
+                    return None;
+                }
+                area_sample(origin, point, edge_u.cross(*edge_v))
+            }
+            Light::Triangle { vertices } => {
+                let edge_u = vertices[1] - vertices[0];
+                let edge_v = vertices[2] - vertices[0];
+                let (point, a, b) = hit_plane(vertices[0], edge_u, edge_v, ray)?;
+                if a < 0.0 || b < 0.0 || a + b > 1.0 {
+                    return None;
+                }
+                area_sample(origin, point, 0.5 * edge_u.cross(edge_v))
+            }
+        }
+    }
+}
+
+/// Adds the lights of `world` transformed by `transform`, as lights of the
+/// object with the id `owner` when they are in an instance of it.
+fn collect_lights(
+    world: &HittableWorld,
+    transform: &Affine3A,
+    owner: Option<usize>,
+    lights: &mut Lights,
+) {
+    let is_light = |material: &Material| matches!(material, Material::DiffuseLight { .. });
+    let object_id = |object_type, index| {
+        owner.unwrap_or_else(|| world.object_id(&HittableObjectIndex::new(object_type, index)))
+    };
+    let parallelogram = |corner: Vec3A, edge_u: Vec3A, edge_v: Vec3A| Light::Parallelogram {
+        corner: transform.transform_point3a(corner),
+        edge_u: transform.transform_vector3a(edge_u),
+        edge_v: transform.transform_vector3a(edge_v),
+    };
+    let triangle = |vertices: [Vec3A; 3]| Light::Triangle {
+        vertices: vertices.map(|vertex| transform.transform_point3a(vertex)),
+    };
+    let scale = uniform_scale(transform);
+
+    for (i, sphere) in world.spheres().iter().enumerate() {
+        if !is_light(sphere.material()) {
+            continue;
+        }
+        match scale {
+            Some(scale) => lights.push(
+                object_id(HittableObjectType::Sphere, i),
+                Light::Sphere {
+                    center: transform.transform_point3a(sphere.center()),
+                    radius: sphere.radius() * scale,
+                },
+            ),
+            None => lights.skipped_spheres += 1,
+        }
+    }
+    for (i, sphere) in world.moving_spheres().iter().enumerate() {
+        if !is_light(sphere.material()) {
+            continue;
+        }
+        match scale {
+            Some(scale) => lights.push(
+                object_id(HittableObjectType::MovingSphere, i),
+                Light::MovingSphere {
+                    center0: transform.transform_point3a(sphere.center0()),
+                    center1: transform.transform_point3a(sphere.center1()),
+                    time0: sphere.time0(),
+                    time1: sphere.time1(),
+                    radius: sphere.radius() * scale,
+                },
+            ),
+            None => lights.skipped_spheres += 1,
+        }
+    }
+    for (i, rectangle) in world.xy_rectangles().iter().enumerate() {
+        if is_light(rectangle.material()) {
+            lights.push(
+                object_id(HittableObjectType::XyRectangle, i),
+                parallelogram(
+                    Vec3A::new(rectangle.x0(), rectangle.y0(), rectangle.k()),
+                    Vec3A::new(rectangle.x1() - rectangle.x0(), 0.0, 0.0),
+                    Vec3A::new(0.0, rectangle.y1() - rectangle.y0(), 0.0),
+                ),
+            );
+        }
+    }
+    for (i, rectangle) in world.xz_rectangles().iter().enumerate() {
+        if is_light(rectangle.material()) {
+            lights.push(
+                object_id(HittableObjectType::XzRectangle, i),
+                parallelogram(
+                    Vec3A::new(rectangle.x0(), rectangle.k(), rectangle.z0()),
+                    Vec3A::new(rectangle.x1() - rectangle.x0(), 0.0, 0.0),
+                    Vec3A::new(0.0, 0.0, rectangle.z1() - rectangle.z0()),
+                ),
+            );
+        }
+    }
+    for (i, rectangle) in world.yz_rectangles().iter().enumerate() {
+        if is_light(rectangle.material()) {
+            lights.push(
+                object_id(HittableObjectType::YzRectangle, i),
+                parallelogram(
+                    Vec3A::new(rectangle.k(), rectangle.y0(), rectangle.z0()),
+                    Vec3A::new(0.0, rectangle.y1() - rectangle.y0(), 0.0),
+                    Vec3A::new(0.0, 0.0, rectangle.z1() - rectangle.z0()),
+                ),
+            );
+        }
+    }
+    for (i, aabb_box) in world.aabb_boxes().iter().enumerate() {
+        if !is_light(aabb_box.material()) {
+            continue;
+        }
+        let id = object_id(HittableObjectType::AabbBox, i);
+        let (min, max) = (aabb_box.box_min(), aabb_box.box_max());
+        let size = max - min;
+        let (x, y, z) = (size * Vec3A::X, size * Vec3A::Y, size * Vec3A::Z);
+        for k in [min, max] {
+            lights.push(
+                id,
+                parallelogram(min * Vec3A::new(1.0, 1.0, 0.0) + k * Vec3A::Z, x, y),
+            );
+            lights.push(
+                id,
+                parallelogram(min * Vec3A::new(1.0, 0.0, 1.0) + k * Vec3A::Y, x, z),
+            );
+            lights.push(
+                id,
+                parallelogram(min * Vec3A::new(0.0, 1.0, 1.0) + k * Vec3A::X, y, z),
+            );
+        }
+    }
+    for (i, t) in world.triangles().iter().enumerate() {
+        if is_light(t.material()) {
+            lights.push(
+                object_id(HittableObjectType::Triangle, i),
+                triangle(t.vertices()),
+            );
+        }
+    }
+    // The triangles of the meshes are numbered mesh after mesh.
+    let mut first_triangle = 0;
+    for mesh in world.triangle_meshes() {
+        if is_light(mesh.material()) {
+            for (i, indices) in mesh.indices().iter().enumerate() {
+                lights.push(
+                    object_id(HittableObjectType::MeshTriangle, first_triangle + i),
+                    triangle(indices.map(|i| mesh.positions()[i as usize])),
+                );
+            }
+        }
+        first_triangle += mesh.triangle_count();
+    }
+    for (i, instance) in world.instances().iter().enumerate() {
+        collect_lights(
+            instance.object(),
+            &(*transform * instance.transform()),
+            Some(object_id(HittableObjectType::Instance, i)),
+            lights,
+        );
+    }
+}
+
+/// Gets the scale of a transform that scales the same along every axis.
+fn uniform_scale(transform: &Affine3A) -> Option<f32> {
+    let matrix = transform.matrix3;
+    let scale = matrix.x_axis.length();
+    let orthogonal = matrix.transpose() * matrix * (1.0 / (scale * scale));
+
+    orthogonal
+        .abs_diff_eq(Mat3A::IDENTITY, 1e-4)
+        .then_some(scale)
+}
+
+/// Samples the cone of directions in which a sphere is seen, or its whole
+/// area from the inside.
+fn sample_sphere(
+    center: Vec3A,
+    radius: f32,
+    origin: Vec3A,
+    rng: &mut impl RngCore,
+) -> Option<LightSample> {
+    let to_center = center - origin;
+    let distance_squared = to_center.length_squared();
+    if distance_squared <= radius * radius {
+        let normal = sample_unit_sphere(rng);
+        let point = center + radius * normal;
+        return area_sample(origin, point, 4.0 * PI * radius * radius * normal);
+    }
+
+    let cos_theta_max = (1.0 - radius * radius / distance_squared).sqrt();
+    let cos_theta = 1.0 + rng.gen::<f32>() * (cos_theta_max - 1.0);
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * rng.gen::<f32>();
+
+    let w = to_center / distance_squared.sqrt();
+    let (u, v) = w.any_orthonormal_pair();
+    let direction = (phi.cos() * sin_theta) * u + (phi.sin() * sin_theta) * v + cos_theta * w;
+
+    // Nearest intersection, or the tangent point if rounding misses it.
+    let half_b = direction.dot(to_center);
+    let discriminant = (half_b * half_b - distance_squared + radius * radius).max(0.0);
+    let point = origin + (half_b - discriminant.sqrt()) * direction;
+
+    Some(LightSample {
+        point,
+        pdf: 1.0 / (2.0 * PI * (1.0 - cos_theta_max)),
+    })
+}
+
+fn sphere_pdf(center: Vec3A, radius: f32, ray: &Ray) -> Option<LightSample> {
+    let origin = ray.origin();
+    let direction = ray.direction().normalize();
+    let to_center = center - origin;
+    let distance_squared = to_center.length_squared();
+    let half_b = direction.dot(to_center);
+    let discriminant = half_b * half_b - distance_squared + radius * radius;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    if distance_squared <= radius * radius {
+        let point = origin + (half_b + discriminant.sqrt()) * direction;
+        let normal = (point - center) / radius;
+        return area_sample(origin, point, 4.0 * PI * radius * radius * normal);
+    }
+
+    let distance = half_b - discriminant.sqrt();
+    if distance <= 0.0 {
+        return None;
+    }
+    let cos_theta_max = (1.0 - radius * radius / distance_squared).sqrt();
+
+    Some(LightSample {
+        point: origin + distance * direction,
+        pdf: 1.0 / (2.0 * PI * (1.0 - cos_theta_max)),
+    })
+}
+
+/// Intersects the plane of a parallelogram or triangle, returning the hit
+/// point and its coordinates along both edges.
+fn hit_plane(corner: Vec3A, edge_u: Vec3A, edge_v: Vec3A, ray: &Ray) -> Option<(Vec3A, f32, f32)> {
+    let normal = edge_u.cross(edge_v);
+    let denominator = normal.dot(ray.direction());
+    if denominator.abs() < f32::EPSILON {
+        return None;
+    }
+
+    let t = normal.dot(corner - ray.origin()) / denominator;
+    if t <= 0.0 {
+        return None;
+    }
+
+    let point = ray.at(t);
+    let w = normal / normal.length_squared();
+    let local = point - corner;
+
+    Some((
+        point,
+        local.cross(edge_v).dot(w),
+        edge_u.cross(local).dot(w),
+    ))
+}
+
+fn sample_unit_sphere(rng: &mut impl RngCore) -> Vec3A {
+    let z = 1.0 - 2.0 * rng.gen::<f32>();
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * rng.gen::<f32>();
+
+    Vec3A::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+/// Converts a point sampled uniformly on an area to a solid angle sample,
+/// `area_normal` is the normal of the surface scaled by its area.
+fn area_sample(origin: Vec3A, point: Vec3A, area_normal: Vec3A) -> Option<LightSample> {
+    let to_point = point - origin;
+    let distance_squared = to_point.length_squared();
+    let cosine_area = to_point.dot(area_normal).abs() / distance_squared.sqrt();
+    if cosine_area <= f32::EPSILON * distance_squared {
+        return None;
+    }
+
+    Some(LightSample {
+        point,
+        pdf: distance_squared / cosine_area,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geometry::hittable_world::HittableWorld;
+    use crate::geometry::instance::Instance;
+    use crate::geometry::sphere::Sphere;
+    use crate::geometry::xz_rectangle::XzRectangle;
+    use crate::light::Lights;
+    use crate::material::Material;
+    use crate::math::color::Color;
+    use glam::{Affine3A, Vec3, Vec3A};
+    use rand_xoshiro::rand_core::SeedableRng;
+    use std::sync::Arc;
+
+    #[test]
+    fn instanced_light_is_transformed_and_sampled() {
+        let mut lamp = HittableWorld::new();
+        let light = Material::new_diffuse_light_color(Color::white());
+        lamp.add_xz_rectangle(XzRectangle::new(light, 0.0, 1.0, 0.0, 1.0, 0.0));
+        lamp.init_bvh_nodes();
+
+        let mut world = HittableWorld::new();
+        world.add_instance(Instance::new(
+            Arc::new(lamp),
+            Affine3A::from_translation(Vec3::new(0.0, 2.0, 0.0)),
+        ));
+        world.init_bvh_nodes();
+
+        let lights = Lights::new(&world);
+        assert_eq!(lights.len(), 1);
+        // The instance is the only object of the world.
+        assert_eq!(lights.of_object(0), lights.as_slice());
+        let lights = lights.as_slice();
+
+        let mut rng = rand_xoshiro::Xoshiro256Plus::seed_from_u64(0);
+        let sample = lights[0].sample(Vec3A::ZERO, 0.0, &mut rng).unwrap();
+        assert!((sample.point.y - 2.0).abs() < 1e-5);
+
+        // The density of the directions integrates to one over the light.
+        let mean_inverse_pdf = (0..10_000)
+            .map(|_| 1.0 / lights[0].sample(Vec3A::ZERO, 0.0, &mut rng).unwrap().pdf)
+            .sum::<f32>()
+            / 10_000.0;
+        let solid_angle = lights_solid_angle();
+        assert!((mean_inverse_pdf - solid_angle).abs() < 0.01 * solid_angle);
+    }
+
+    #[test]
+    fn spheres_in_non_uniformly_scaled_instances_are_skipped() {
+        let mut lamp = HittableWorld::new();
+        let light = Material::new_diffuse_light_color(Color::white());
+        lamp.add_sphere(Sphere::new(Vec3A::ZERO, 1.0, light));
+        lamp.init_bvh_nodes();
+        let lamp = Arc::new(lamp);
+
+        let mut world = HittableWorld::new();
+        for scale in [Vec3::splat(2.0), Vec3::new(1.0, 2.0, 1.0)] {
+            world.add_instance(Instance::new(lamp.clone(), Affine3A::from_scale(scale)));
+        }
+        world.init_bvh_nodes();
+
+        let lights = Lights::new(&world);
+        assert_eq!(lights.len(), 1);
+        assert_eq!(lights.skipped_spheres(), 1);
+    }
+
+    /// Solid angle of the unit square at a height of 2 seen from its corner.
+    fn lights_solid_angle() -> f32 {
+        let n = 400;
+        let cell = 1.0 / n as f32;
+        (0..n * n)
+            .map(|i| {
+                let x = ((i % n) as f32 + 0.5) * cell;
+                let z = ((i / n) as f32 + 0.5) * cell;
+                let distance_squared: f32 = x * x + 4.0 + z * z;
+                2.0 * cell * cell / distance_squared.powf(1.5)
+            })
+            .sum()
+    }
+}
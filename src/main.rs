@@ -1,9 +1,10 @@
 use raytracing::run;
+use std::process::ExitCode;
 use tracy_full::alloc::GlobalAllocator;
 
 #[global_allocator]
 static ALLOC: GlobalAllocator = GlobalAllocator::new();
 
-fn main() {
-    run();
+fn main() -> ExitCode {
+    run()
 }
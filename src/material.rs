@@ -6,12 +6,16 @@ use crate::texture::Texture;
 use glam::Vec3A;
 use rand::Rng;
 use rand_xoshiro::rand_core::RngCore;
+use std::f32::consts::{FRAC_1_PI, PI};
 use tracy_full::zone;
 
 #[derive(Default)]
 pub struct ScatterResult {
     pub attenuation: Color,
     pub scattered: Ray,
+    /// Solid angle density with which the scattered direction was sampled,
+    /// `None` for specular scattering that lights can not be sampled for.
+    pub pdf: Option<f32>,
 }
 
 impl ScatterResult {
@@ -19,6 +23,17 @@ impl ScatterResult {
         Self {
             attenuation,
             scattered,
+            pdf: None,
+        }
+    }
+
+    /// Creates a result whose direction was sampled proportionally to
+    /// [`Material::scattering_pdf`], with the given density.
+    pub fn new_diffuse(attenuation: Color, scattered: Ray, pdf: f32) -> Self {
+        Self {
+            attenuation,
+            scattered,
+            pdf: Some(pdf),
         }
     }
 }
@@ -78,6 +93,38 @@ impl Material {
         }
     }
 
+    /// Density with which [`Material::scatter`] samples `direction`, so that
+    /// the light reflected from a direction is the attenuation times this
+    /// density.
+    ///
+    /// Zero for the specular materials, whose scattering is a single direction.
+    pub fn scattering_pdf(&self, ray_in: &Ray, record: &HitRecord, direction: Vec3A) -> f32 {
+        match self {
+            Material::Lambertian { albedo: _ } => lambertian_pdf(record.normal(), direction),
+            Material::Metal { albedo: _, fuzz } if *fuzz > 0.0 => {
+                if direction.dot(record.normal()) <= 0.0 {
+                    return 0.0;
+                }
+                let reflected = ray_in.direction().normalize().reflect(record.normal());
+                metal_pdf(reflected, *fuzz, direction)
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Fraction of the light reflected at a point, white for dielectrics and
+    /// black for lights.
+    pub fn albedo(&self, u: f32, v: f32, point: Vec3A) -> Color {
+        match self {
+            Material::Lambertian { albedo } => albedo.value(u, v, point),
+            Material::Metal { albedo, fuzz: _ } => *albedo,
+            Material::Dielectric {
+                refraction_index: _,
+            } => Color::white(),
+            Material::DiffuseLight { emit: _ } => Color::black(),
+        }
+    }
+
     pub fn emit(&self, u: f32, v: f32, point: Vec3A) -> Color {
         zone!();
         match self {
@@ -101,8 +148,17 @@ fn scatter_lambertian(
     let mut scattered = Ray::new(record.point(), scatter_direction);
     scattered.time = ray_in.time;
     let attenuation = albedo.value(record.u(), record.v(), record.point());
+    let pdf = lambertian_pdf(record.normal(), scatter_direction);
 
-    Some(ScatterResult::new(attenuation, scattered))
+    Some(ScatterResult::new_diffuse(attenuation, scattered, pdf))
+}
+
+/// Cosine weighted density, the one of the directions sampled by
+/// [`scatter_lambertian`].
+fn lambertian_pdf(normal: Vec3A, direction: Vec3A) -> f32 {
+    let cosine = normal.dot(direction.normalize());
+
+    cosine.max(0.0) * FRAC_1_PI
 }
 
 fn scatter_metal(
@@ -120,13 +176,38 @@ fn scatter_metal(
     );
     scattered.time = ray_in.time;
 
-    if scattered.direction().dot(record.normal()) > 0.0 {
-        Some(ScatterResult::new(*albedo, scattered))
-    } else {
+    if scattered.direction().dot(record.normal()) <= 0.0 {
         None
+    } else if fuzz > 0.0 {
+        let pdf = metal_pdf(reflected, fuzz, scattered.direction());
+        Some(ScatterResult::new_diffuse(*albedo, scattered, pdf))
+    } else {
+        Some(ScatterResult::new(*albedo, scattered))
     }
 }
 
+/// Density of the directions sampled by [`scatter_metal`], the ones towards
+/// uniform points of the ball of radius `fuzz` around the reflected direction.
+fn metal_pdf(reflected: Vec3A, fuzz: f32, direction: Vec3A) -> f32 {
+    let direction = direction.normalize();
+    // The center of the ball is at `|direction × reflected|` from the line of
+    // the direction, which avoids the cancellation of the usual discriminant.
+    let discriminant = fuzz * fuzz - direction.cross(reflected).length_squared();
+    if discriminant <= 0.0 {
+        return 0.0;
+    }
+
+    // Integral of t² along the chord of the ball, over its volume.
+    let half_b = direction.dot(reflected);
+    let root = discriminant.sqrt();
+    let far = half_b + root;
+    let near = (half_b - root).max(0.0);
+    let chord = if near > 0.0 { 2.0 * root } else { far };
+    let volume = 4.0 / 3.0 * PI * fuzz * fuzz * fuzz;
+
+    chord * (far * far + far * near + near * near) / (3.0 * volume)
+}
+
 fn scatter_dielectrics(
     refraction_index: f32,
     ray_in: &Ray,
@@ -4,7 +4,7 @@ use rand::Rng;
 use rand_xoshiro::rand_core::RngCore;
 use std::ops::{Add, AddAssign, Index, Mul, MulAssign, Range};
 
-#[derive(Default, Debug, Copy, Clone)]
+#[derive(Default, Debug, Copy, Clone, PartialEq)]
 pub struct Color {
     pub x: f32,
     pub y: f32,
@@ -41,6 +41,23 @@ impl Color {
         self.x * other.x + self.y * other.y + self.z * other.z
     }
 
+    /// Luminance of a linear Rec. 709 color.
+    pub fn luminance(&self) -> f32 {
+        0.2126 * self.x + 0.7152 * self.y + 0.0722 * self.z
+    }
+
+    pub fn max_component(&self) -> f32 {
+        self.x.max(self.y).max(self.z)
+    }
+
+    pub fn clamp(&self, min: f32, max: f32) -> Self {
+        Self::new(
+            self.x.clamp(min, max),
+            self.y.clamp(min, max),
+            self.z.clamp(min, max),
+        )
+    }
+
     pub const fn black() -> Self {
         Self::new(0.0, 0.0, 0.0)
     }
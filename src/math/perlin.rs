@@ -1,7 +1,7 @@
 use crate::math::vec3::Vec3Ext;
 use glam::Vec3A;
 use rand::Rng;
-use rand_xoshiro::rand_core::RngCore;
+use rand_xoshiro::rand_core::{RngCore, SeedableRng};
 
 pub const POINT_COUNT: usize = 256;
 
@@ -11,6 +11,7 @@ pub struct Perlin {
     perm_x: [i32; POINT_COUNT],
     perm_y: [i32; POINT_COUNT],
     perm_z: [i32; POINT_COUNT],
+    seed: Option<u32>,
 }
 
 impl Perlin {
@@ -25,9 +26,26 @@ impl Perlin {
             perm_x: Perlin::generate_perm(rng),
             perm_y: Perlin::generate_perm(rng),
             perm_z: Perlin::generate_perm(rng),
+            seed: None,
         }
     }
 
+    /// Creates a noise generator whose tables are derived from `seed`.
+    ///
+    /// Unlike [`Perlin::new`], the seed is remembered so the noise can be
+    /// written back to a scene description.
+    pub fn from_seed(seed: u32) -> Self {
+        let mut rng = rand_xoshiro::Xoshiro256Plus::seed_from_u64(seed.into());
+        let mut perlin = Self::new(&mut rng);
+        perlin.seed = Some(seed);
+
+        perlin
+    }
+
+    pub fn seed(&self) -> Option<u32> {
+        self.seed
+    }
+
     pub fn noise(&self, p: Vec3A) -> f32 {
         let u = p.x - p.x.floor();
         let v = p.y - p.y.floor();
@@ -0,0 +1,381 @@
+use crate::aov::{Aov, Aovs};
+use crate::framebuffer::Framebuffer;
+use crate::math::color::Color;
+use crate::tone_mapping::ToneMapping;
+use clap::ValueEnum;
+use exr::prelude::f16;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Type of the channels of the OpenEXR images.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ExrPrecision {
+    /// 16 bits floats.
+    Half,
+    /// 32 bits floats.
+    Float,
+}
+
+/// Format of an image file, chosen from its extension.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    /// OpenEXR, with linear channels.
+    Exr,
+    /// Radiance HDR, with linear RGBE pixels.
+    Hdr,
+}
+
+impl ImageFormat {
+    pub fn from_path(path: &Path) -> Result<Self, OutputError> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("png") => Ok(Self::Png),
+            Some("exr") => Ok(Self::Exr),
+            Some("hdr") => Ok(Self::Hdr),
+            _ => Err(OutputError::UnknownFormat(path.to_path_buf())),
+        }
+    }
+}
+
+/// Settings of the conversion of a [`Framebuffer`] to an image file.
+#[derive(Debug, Clone)]
+pub struct OutputSettings {
+    /// Exposure of the 8 bits images, in stops.
+    pub exposure: f32,
+    /// Operator mapping the radiance of the 8 bits images to their range.
+    pub tone_mapping: ToneMapping,
+    pub exr_precision: ExrPrecision,
+}
+
+impl Default for OutputSettings {
+    fn default() -> Self {
+        Self {
+            exposure: 0.0,
+            tone_mapping: ToneMapping::Clamp,
+            exr_precision: ExrPrecision::Half,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum OutputError {
+    UnknownFormat(PathBuf),
+    Io(std::io::Error),
+    Png(png::EncodingError),
+    Exr(exr::error::Error),
+}
+
+impl Display for OutputError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutputError::UnknownFormat(path) => write!(
+                f,
+                "Unknown image format of `{}`, use a .png, .exr or .hdr file",
+                path.display()
+            ),
+            OutputError::Io(err) => write!(f, "{err}"),
+            OutputError::Png(err) => write!(f, "{err}"),
+            OutputError::Exr(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for OutputError {}
+
+impl From<std::io::Error> for OutputError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<png::EncodingError> for OutputError {
+    fn from(err: png::EncodingError) -> Self {
+        Self::Png(err)
+    }
+}
+
+impl From<exr::error::Error> for OutputError {
+    fn from(err: exr::error::Error) -> Self {
+        Self::Exr(err)
+    }
+}
+
+/// Writes the framebuffer to `path` in the [`ImageFormat`] of its extension.
+///
+/// Only the PNG images are tone mapped, the others keep the linear radiance.
+/// The [`Aovs`] of the framebuffer are layers of the OpenEXR images, and are
+/// otherwise written next to the image, see [`aov_path`].
+pub fn write_image(
+    framebuffer: &Framebuffer,
+    path: &Path,
+    settings: &OutputSettings,
+) -> Result<(), OutputError> {
+    let format = ImageFormat::from_path(path)?;
+    match format {
+        ImageFormat::Png => write_png(framebuffer, path, settings)?,
+        ImageFormat::Exr => write_exr(framebuffer, path, settings.exr_precision)?,
+        ImageFormat::Hdr => write_hdr(framebuffer, path)?,
+    }
+
+    match framebuffer.aovs() {
+        Some(aovs) if format != ImageFormat::Exr => write_aov_images(aovs, path, format, settings),
+        _ => Ok(()),
+    }
+}
+
+/// Gets the path of the image of an output variable, `out.albedo.png` for the
+/// albedo of `out.png`.
+pub fn aov_path(path: &Path, aov: Aov) -> PathBuf {
+    suffixed_path(path, aov.name())
+}
+
+/// Gets the path of the denoised image, `out.denoised.png` for `out.png`.
+pub fn denoised_path(path: &Path) -> PathBuf {
+    suffixed_path(path, "denoised")
+}
+
+/// Gets the path of the sample heatmap, always a PNG image,
+/// `out.samples.png` for `out.exr`.
+pub fn sample_heatmap_path(path: &Path) -> PathBuf {
+    path.with_extension("samples.png")
+}
+
+fn suffixed_path(path: &Path, suffix: &str) -> PathBuf {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy())
+        .unwrap_or_default();
+
+    path.with_extension(format!("{suffix}.{extension}"))
+}
+
+/// Writes a visualization of each output variable, with the tone mapping of
+/// the image only for the emission.
+fn write_aov_images(
+    aovs: &Aovs,
+    path: &Path,
+    format: ImageFormat,
+    settings: &OutputSettings,
+) -> Result<(), OutputError> {
+    let untouched = OutputSettings {
+        exposure: 0.0,
+        tone_mapping: ToneMapping::Clamp,
+        ..settings.clone()
+    };
+
+    for aov in Aov::ALL {
+        let image = Framebuffer::from_pixels(aovs.width(), aovs.height(), aovs.visualize(aov));
+        let aov_path = aov_path(path, aov);
+        let settings = if aov == Aov::Emission {
+            settings
+        } else {
+            &untouched
+        };
+
+        match format {
+            ImageFormat::Png => write_png(&image, &aov_path, settings)?,
+            ImageFormat::Exr => write_exr(&image, &aov_path, settings.exr_precision)?,
+            ImageFormat::Hdr => write_hdr(&image, &aov_path)?,
+        }
+    }
+
+    Ok(())
+}
+
+pub fn write_png(
+    framebuffer: &Framebuffer,
+    path: &Path,
+    settings: &OutputSettings,
+) -> Result<(), OutputError> {
+    let file = File::create(path)?;
+    let w = &mut BufWriter::new(file);
+
+    let mut encoder = png::Encoder::new(w, framebuffer.width() as u32, framebuffer.height() as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&framebuffer.to_srgb8(settings.exposure, settings.tone_mapping))?;
+
+    Ok(())
+}
+
+/// Writes an OpenEXR image, whose [`Aovs`] are the layers following the
+/// `beauty` one of the radiance.
+pub fn write_exr(
+    framebuffer: &Framebuffer,
+    path: &Path,
+    precision: ExrPrecision,
+) -> Result<(), OutputError> {
+    if let Some(aovs) = framebuffer.aovs() {
+        return write_exr_layers(framebuffer, aovs, path, precision);
+    }
+
+    let (width, height) = (framebuffer.width(), framebuffer.height());
+    match precision {
+        ExrPrecision::Half => exr::prelude::write_rgb_file(path, width, height, |x, y| {
+            let pixel = framebuffer.pixel(x, y);
+            (
+                f16::from_f32(pixel.x),
+                f16::from_f32(pixel.y),
+                f16::from_f32(pixel.z),
+            )
+        })?,
+        ExrPrecision::Float => exr::prelude::write_rgb_file(path, width, height, |x, y| {
+            let pixel = framebuffer.pixel(x, y);
+            (pixel.x, pixel.y, pixel.z)
+        })?,
+    }
+
+    Ok(())
+}
+
+fn write_exr_layers(
+    framebuffer: &Framebuffer,
+    aovs: &Aovs,
+    path: &Path,
+    precision: ExrPrecision,
+) -> Result<(), OutputError> {
+    use exr::prelude::*;
+
+    let size = (framebuffer.width(), framebuffer.height());
+    let float_channel = |name: &str, values: Vec<f32>| {
+        let samples = match precision {
+            ExrPrecision::Half => FlatSamples::F16(values.into_iter().map(f16::from_f32).collect()),
+            ExrPrecision::Float => FlatSamples::F32(values),
+        };
+        AnyChannel::new(name, samples)
+    };
+    let vector_channels = |names: [&str; 3], vectors: Vec<[f32; 3]>| {
+        (0..3)
+            .map(|k| float_channel(names[k], vectors.iter().map(|v| v[k]).collect()))
+            .collect::<Vec<_>>()
+    };
+    let color_channels = |colors: Vec<Color>| {
+        let vectors = colors.iter().map(|c| [c.x, c.y, c.z]).collect();
+        vector_channels(["R", "G", "B"], vectors)
+    };
+    let layer = |name: &str, channels: Vec<AnyChannel<FlatSamples>>| {
+        Layer::new(
+            size,
+            LayerAttributes::named(name),
+            Encoding::FAST_LOSSLESS,
+            AnyChannels::sort(channels.into()),
+        )
+    };
+
+    let pixels = aovs.pixels();
+    let mut layers = vec![layer(
+        "beauty",
+        color_channels(framebuffer.pixels().to_vec()),
+    )];
+    for aov in Aov::ALL {
+        let channels = match aov {
+            Aov::Albedo => color_channels(pixels.iter().map(|p| p.albedo).collect()),
+            Aov::Normal => vector_channels(
+                ["X", "Y", "Z"],
+                pixels.iter().map(|p| p.normal.to_array()).collect(),
+            ),
+            // Depths keep full precision, which halves lose with the distance.
+            Aov::Depth => vec![AnyChannel::new(
+                "Z",
+                FlatSamples::F32(pixels.iter().map(|p| p.depth).collect()),
+            )],
+            Aov::ObjectId => vec![AnyChannel::new(
+                "id",
+                FlatSamples::U32(pixels.iter().map(|p| p.object_id).collect()),
+            )],
+            Aov::Emission => color_channels(pixels.iter().map(|p| p.emission).collect()),
+        };
+        layers.push(layer(aov.name(), channels));
+    }
+
+    let attributes = ImageAttributes::new(IntegerBounds::from_dimensions(size));
+    Image::from_layers(attributes, Layers::from_vec(layers))
+        .write()
+        .to_file(path)?;
+
+    Ok(())
+}
+
+/// Writes a Radiance HDR image with uncompressed RGBE pixels.
+pub fn write_hdr(framebuffer: &Framebuffer, path: &Path) -> Result<(), OutputError> {
+    let file = File::create(path)?;
+    let mut w = BufWriter::new(file);
+
+    write!(
+        w,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        framebuffer.height(),
+        framebuffer.width()
+    )?;
+    for pixel in framebuffer.pixels() {
+        w.write_all(&to_rgbe(pixel))?;
+    }
+    w.flush()?;
+
+    Ok(())
+}
+
+/// Encodes a color as three mantissas sharing the exponent of the largest
+/// channel.
+fn to_rgbe(color: &Color) -> [u8; 4] {
+    let color = Color::new(color.x.max(0.0), color.y.max(0.0), color.z.max(0.0));
+    let max = color.max_component();
+    if max < 1e-32 || !max.is_finite() {
+        return [0; 4];
+    }
+
+    // `max` is in [2^(exponent - 1), 2^exponent[, the mantissas below 256.
+    let exponent = max.log2().floor() as i32 + 1;
+    let scale = 256.0 / 2.0f32.powi(exponent);
+    let mantissa = |value: f32| (value * scale).min(255.0) as u8;
+
+    [
+        mantissa(color.x),
+        mantissa(color.y),
+        mantissa(color.z),
+        (exponent + 128) as u8,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::framebuffer::Framebuffer;
+    use crate::math::color::Color;
+    use crate::output::{to_rgbe, write_exr, ExrPrecision};
+
+    #[test]
+    fn rgbe_shares_the_largest_exponent() {
+        assert_eq!(to_rgbe(&Color::new(1.0, 0.5, 0.25)), [128, 64, 32, 129]);
+        assert_eq!(to_rgbe(&Color::new(15.0, 0.0, 0.0)), [240, 0, 0, 132]);
+        assert_eq!(to_rgbe(&Color::black()), [0; 4]);
+    }
+
+    #[test]
+    fn exr_keeps_values_above_one() {
+        let pixels = vec![Color::new(15.0, 0.5, 0.0), Color::new(0.25, 1000.0, 2.0)];
+        let framebuffer = Framebuffer::from_pixels(2, 1, pixels.clone());
+        let path = std::env::temp_dir().join("raytracing_exr_keeps_values_above_one.exr");
+
+        write_exr(&framebuffer, &path, ExrPrecision::Float).unwrap();
+        let image = exr::prelude::read_first_rgba_layer_from_file(
+            &path,
+            |resolution, _| vec![Color::black(); resolution.width() * resolution.height()],
+            |pixels, position, (r, g, b, _): (f32, f32, f32, f32)| {
+                pixels[position.y() * 2 + position.x()] = Color::new(r, g, b);
+            },
+        )
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(image.layer_data.channel_data.pixels, pixels);
+    }
+}
@@ -1,90 +1,1289 @@
 use rand::Rng;
-use rand_xoshiro::rand_core::{RngCore, SeedableRng};
+use rand_xoshiro::rand_core::SeedableRng;
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::sync::LazyLock;
 
-use crate::consts::{MAX_DEPTH, SAMPLES_PER_PIXEL};
+use crate::aov::{AovPixel, Aovs};
+use crate::camera::Camera;
+use crate::cancellation::CancellationToken;
+use crate::checkpoint::{fingerprint, CheckpointError, CheckpointReader, CheckpointWriter};
+use crate::consts::{
+    IMAGE_HEIGHT, IMAGE_WIDTH, MAX_DEPTH, RUSSIAN_ROULETTE_DEPTH, SAMPLES_PER_PIXEL, TILE_SIZE,
+};
+use crate::film::{Film, FilmTile, Filter};
+use crate::framebuffer::Framebuffer;
+use crate::geometry::hit::HitRecord;
 use crate::geometry::hittable_world::HittableWorld;
+use crate::light::{Light, Lights};
 use crate::math::color::Color;
 use crate::ray::Ray;
+use crate::sampler::{
+    light_dimensions, roulette_dimensions, scattering_dimensions, IndependentSampler,
+    PointSetSampler, Sampler, SamplerKind, PIXEL_DIMENSIONS,
+};
 use crate::scene::Scene;
+use crate::tile::{tiles, Tile, TileOrder};
+use glam::Vec3A;
+use tracy_full::zone;
+
+/// Settings of a single render, independent of the rendered [`Scene`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenderSettings {
+    pub image_width: usize,
+    pub image_height: usize,
+    pub samples_per_pixel: u32,
+    /// Maximum number of bounces of a path.
+    pub max_depth: u32,
+    /// Number of bounces after which a path is continued with a probability
+    /// given by its throughput, whose contribution is scaled to compensate.
+    pub russian_roulette_depth: u32,
+    /// Seed from which the random streams of the render are derived.
+    pub seed: u64,
+    /// Maximum value of each channel of a sample, used to remove fireflies.
+    pub clamp: Option<f32>,
+    /// Samples the lights of the scene at diffuse hits instead of only
+    /// finding them by bouncing.
+    pub sample_lights: bool,
+    /// Renders the [`Aovs`] of the first hits of the camera rays next to
+    /// the image.
+    pub aovs: bool,
+    /// Stops sampling the pixels which converged before `samples_per_pixel`.
+    pub adaptive_sampling: Option<AdaptiveSampling>,
+    /// Width and height in pixels of the tiles rendered by each thread.
+    pub tile_size: usize,
+    pub tile_order: TileOrder,
+    /// Reconstruction filter weighting the samples in the pixels around them.
+    pub filter: Filter,
+    /// Distribution of the random numbers of the samples of each pixel.
+    pub sampler: SamplerKind,
+}
+
+impl RenderSettings {
+    pub fn new(image_width: usize, image_height: usize) -> Self {
+        Self {
+            image_width,
+            image_height,
+            ..Default::default()
+        }
+    }
+
+    pub fn aspect_ratio(&self) -> f32 {
+        self.image_width as f32 / self.image_height as f32
+    }
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            image_width: IMAGE_WIDTH,
+            image_height: IMAGE_HEIGHT,
+            samples_per_pixel: SAMPLES_PER_PIXEL,
+            max_depth: MAX_DEPTH,
+            russian_roulette_depth: RUSSIAN_ROULETTE_DEPTH,
+            seed: 0,
+            clamp: None,
+            sample_lights: true,
+            aovs: false,
+            adaptive_sampling: None,
+            tile_size: TILE_SIZE,
+            tile_order: TileOrder::Hilbert,
+            filter: Filter::default(),
+            sampler: SamplerKind::Independent,
+        }
+    }
+}
+
+/// Criterion stopping the sampling of a pixel once its mean is precise
+/// enough.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AdaptiveSampling {
+    /// Number of samples of every pixel, before its noise is estimated.
+    pub min_samples: u32,
+    /// Standard error of the mean luminance of a pixel, relative to it, under
+    /// which the pixel is converged.
+    pub threshold: f32,
+}
+
+impl Default for AdaptiveSampling {
+    fn default() -> Self {
+        Self {
+            min_samples: 16,
+            threshold: 0.02,
+        }
+    }
+}
+
+/// Running mean and variance of the luminance of the samples of a pixel.
+#[derive(Debug, Clone, Default)]
+struct LuminanceStats {
+    count: u32,
+    mean: f32,
+    /// Sum of the squared differences to the mean.
+    m2: f32,
+}
+
+impl LuminanceStats {
+    fn add(&mut self, luminance: f32) {
+        self.count += 1;
+        let delta = luminance - self.mean;
+        self.mean += delta / self.count as f32;
+        self.m2 += delta * (luminance - self.mean);
+    }
+
+    /// Standard error of the mean relative to it, the darkest pixels being
+    /// compared to a luminance of 0.01 so that they can converge.
+    fn relative_error(&self) -> f32 {
+        if self.count < 2 {
+            return f32::INFINITY;
+        }
+
+        let variance = self.m2 / (self.count - 1) as f32;
+        (variance / self.count as f32).sqrt() / self.mean.max(0.01)
+    }
+}
+
+/// Relative distance before a sampled light point at which a shadow ray hit
+/// is considered an occluder rather than the light itself.
+const SHADOW_EPSILON: f32 = 1e-3;
+
+/// Lights of the renders which do not sample them.
+static NO_LIGHTS: LazyLock<Lights> = LazyLock::new(Lights::default);
 
 /// Gets the color of the provided ray.
 ///
+/// At each hit with a non specular material, one of the `lights` is sampled
+/// with a shadow ray as well as the material, and both estimates of the
+/// emission are combined with the power heuristic.
+///
 /// # Arguments
 ///
 /// * `ray`: Ray to get the color of.
 /// * `hittable_list`: List of hittable objects to check the ray on.
+/// * `lights`: Lights sampled at non specular hits, empty to only find light by bouncing.
+/// * `max_depth`: Maximum number of bounces of the ray.
+/// * `russian_roulette_depth`: Number of bounces after which the path is
+///   randomly terminated.
 ///
 /// returns: Vec3
 fn ray_color(
     mut ray: Ray,
     background_color: &Color,
     hittable_list: &HittableWorld,
-    rng: &mut impl RngCore,
+    lights: &Lights,
+    max_depth: u32,
+    russian_roulette_depth: u32,
+    sampler: &mut impl Sampler,
 ) -> Color {
     let mut color = Color::white();
     let mut emitted = Color::black();
+    // Density with which the material sampled `ray`, when the lights were
+    // also sampled from its origin.
+    let mut scattering_pdf = None;
 
-    for _ in 0..MAX_DEPTH {
-        let record = hittable_list.hit_no_limit(&ray);
+    for depth in 0..max_depth {
+        let hit = hittable_list.hit_object(&ray, 0.001, f32::INFINITY);
 
-        if record.is_none() {
-            return *background_color * color;
+        if hit.is_none() {
+            return emitted + *background_color * color;
         }
-        let record = record.unwrap();
+        let (index, record) = hit.unwrap();
         let emit = record
             .material()
             .emit(record.u(), record.v(), record.point());
-        emitted += color * emit;
+        if emit.dot(&emit) > 0.0 {
+            let weight = match scattering_pdf {
+                Some(scattering_pdf) => {
+                    let object_lights = lights.of_object(hittable_list.object_id(&index));
+                    let light_pdf = light_pdf(object_lights, &ray, &record) / lights.len() as f32;
+                    power_heuristic(scattering_pdf, light_pdf)
+                }
+                None => 1.0,
+            };
+            emitted += color * emit * weight;
+        }
 
-        let scatter = record.material().scatter(&ray, &record, rng);
+        sampler.start_dimensions(scattering_dimensions(depth));
+        let scatter = record.material().scatter(&ray, &record, sampler);
         if scatter.is_none() {
             return emitted;
         }
 
         let scatter = scatter.unwrap();
+        // The light sampled here is reached by the same number of bounces as
+        // the emission of the next hit.
+        scattering_pdf = None;
+        if !lights.is_empty() && depth + 1 < max_depth {
+            if let Some(pdf) = scatter.pdf {
+                sampler.start_dimensions(light_dimensions(depth));
+                let direct = sample_direct_light(&ray, &record, hittable_list, lights, sampler);
+                emitted += color * scatter.attenuation * direct;
+                scattering_pdf = Some(pdf);
+            }
+        }
+
         color *= scatter.attenuation;
         ray = scatter.scattered;
 
-        if color.dot(&color) < 0.0001 {
-            return emitted;
+        if depth >= russian_roulette_depth {
+            let survival = color.max_component().min(1.0);
+            sampler.start_dimensions(roulette_dimensions(depth));
+            if survival <= 0.0 || sampler.gen::<f32>() >= survival {
+                return emitted;
+            }
+            color *= 1.0 / survival;
         }
     }
 
     emitted
 }
 
-pub fn render(scene: &Scene, image_width: usize, image_height: usize) -> Vec<u8> {
-    (0..image_height)
-        .into_par_iter()
-        .rev()
-        .flat_map(|j| {
-            let mut rng = rand_xoshiro::Xoshiro256Plus::from_entropy();
-            (0..image_width)
-                .flat_map(|i| {
-                    let mut pixel_color = Color::black();
-                    for _ in 0..SAMPLES_PER_PIXEL {
-                        let u = (i as f32 + rng.gen::<f32>()) / (image_width as f32 - 1.0);
-                        let v = (j as f32 + rng.gen::<f32>()) / (image_height as f32 - 1.0);
-                        let ray = scene.camera().get_ray(u, v, &mut rng);
-
-                        pixel_color += ray_color(
-                            ray,
-                            scene.background_color(),
-                            scene.hittable_list(),
-                            &mut rng,
-                        );
-                    }
-
-                    const SCALE: f32 = 1.0 / SAMPLES_PER_PIXEL as f32;
-                    (0..3)
-                        .map(|k| {
-                            (256.0 * (pixel_color[k as usize] * SCALE).sqrt().clamp(0.0, 0.999))
-                                as u8
-                        })
-                        .collect::<Vec<u8>>()
-                })
-                .collect::<Vec<u8>>()
+/// Estimates the light received from one randomly chosen light at a non
+/// specular hit, weighted by the scattering density of its material and by
+/// the power heuristic against sampling the material.
+fn sample_direct_light(
+    ray: &Ray,
+    record: &HitRecord,
+    hittable_list: &HittableWorld,
+    lights: &Lights,
+    sampler: &mut impl Sampler,
+) -> Color {
+    zone!();
+    let light = &lights.as_slice()[sampler.gen_range(0..lights.len())];
+    let Some(sample) = light.sample(record.point(), ray.time, sampler) else {
+        return Color::black();
+    };
+
+    let to_light = sample.point - record.point();
+    let distance = to_light.length();
+    let direction = to_light / distance;
+    let scattering_pdf = record.material().scattering_pdf(ray, record, direction);
+    if scattering_pdf <= 0.0 {
+        return Color::black();
+    }
+
+    let mut shadow_ray = Ray::new(record.point(), direction);
+    shadow_ray.time = ray.time;
+    match hittable_list.hit_no_limit(&shadow_ray) {
+        Some(hit) if hit.t() > distance * (1.0 - SHADOW_EPSILON) => {
+            let emit = hit.material().emit(hit.u(), hit.v(), hit.point());
+            let light_pdf = sample.pdf / lights.len() as f32;
+            let weight = power_heuristic(light_pdf, scattering_pdf);
+            emit * (weight * scattering_pdf / light_pdf)
+        }
+        _ => Color::black(),
+    }
+}
+
+/// Sum of the densities with which `lights`, the ones of the object hit at
+/// `record`, are sampled in the direction of `ray`, counting only the ones it
+/// reaches at the hit.
+fn light_pdf(lights: &[Light], ray: &Ray, record: &HitRecord) -> f32 {
+    zone!();
+    let tolerance = SHADOW_EPSILON * (record.point() - ray.origin()).length();
+
+    lights
+        .iter()
+        .filter_map(|light| light.pdf(ray))
+        .filter(|sample| (sample.point - record.point()).length() <= tolerance)
+        .map(|sample| sample.pdf)
+        .sum()
+}
+
+/// Weight of a sample of density `pdf` against one of the other strategy,
+/// of density `other_pdf`, in a multiple importance sampling estimate.
+fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    let pdf = pdf * pdf;
+    let other_pdf = other_pdf * other_pdf;
+
+    if pdf + other_pdf > 0.0 {
+        pdf / (pdf + other_pdf)
+    } else {
+        0.0
+    }
+}
+
+/// Gets the output variables of the first hit of a camera ray.
+fn first_hit_aov(ray: &Ray, hittable_list: &HittableWorld) -> AovPixel {
+    zone!();
+    let Some((index, record)) = hittable_list.hit_object(ray, 0.001, f32::INFINITY) else {
+        return AovPixel::default();
+    };
+    let material = record.material();
+
+    AovPixel {
+        albedo: material.albedo(record.u(), record.v(), record.point()),
+        normal: record.normal(),
+        depth: record.t() * ray.direction().length(),
+        object_id: hittable_list.object_id(&index) as u32,
+        emission: material.emit(record.u(), record.v(), record.point()),
+    }
+}
+
+/// Running sums of the output variables of the samples of a pixel.
+#[derive(Debug, Clone, Default)]
+struct AovSum {
+    count: u32,
+    albedo: Color,
+    normal: Vec3A,
+    emission: Color,
+    /// Sum of the depths of the samples which hit an object.
+    depth: f32,
+    hits: u32,
+    /// Object of the first sample.
+    object_id: Option<u32>,
+}
+
+impl AovSum {
+    fn add(&mut self, sample: &AovPixel) {
+        self.count += 1;
+        self.albedo += sample.albedo;
+        self.normal += sample.normal;
+        self.emission += sample.emission;
+        if sample.depth.is_finite() {
+            self.depth += sample.depth;
+            self.hits += 1;
+        }
+        self.object_id.get_or_insert(sample.object_id);
+    }
+
+    fn average(&self) -> AovPixel {
+        let Some(object_id) = self.object_id else {
+            return AovPixel::default();
+        };
+        let scale = 1.0 / self.count as f32;
+
+        AovPixel {
+            albedo: self.albedo * scale,
+            normal: self.normal.normalize_or_zero(),
+            depth: if self.hits > 0 {
+                self.depth / self.hits as f32
+            } else {
+                f32::INFINITY
+            },
+            object_id,
+            emission: self.emission * scale,
+        }
+    }
+}
+
+/// Creates the random generator of a sample of a pixel.
+///
+/// Each sample has its own stream derived from the render seed, so the image
+/// does not depend on the order in which threads render the pixels, and a
+/// render can be resumed from the sample counts of its pixels.
+fn sample_rng(seed: u64, pixel_index: usize, sample_index: u32) -> rand_xoshiro::Xoshiro256Plus {
+    // Spreads consecutive indices before SplitMix64 expands the seed.
+    const GOLDEN_GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+    let sample = ((pixel_index as u64) << 32) | sample_index as u64;
+    let stream = sample.wrapping_add(1).wrapping_mul(GOLDEN_GAMMA);
+
+    rand_xoshiro::Xoshiro256Plus::seed_from_u64(seed ^ stream)
+}
+
+/// Renders the linear radiance of the pixels of the scene, with their
+/// [`Aovs`] when enabled by the settings, and the number of samples of each
+/// pixel.
+///
+/// The aspect ratio of the scene camera is replaced by the one of the image.
+pub fn render(scene: &Scene, settings: &RenderSettings) -> Framebuffer {
+    let mut renderer = ProgressiveRenderer::new(scene, settings);
+    renderer.render_pass(settings.samples_per_pixel);
+
+    renderer.framebuffer()
+}
+
+/// Renders like [`render`] until `cancellation` is cancelled, giving the
+/// image of the samples taken so far.
+///
+/// The samples are taken in passes of one sample per pixel, so that the
+/// pixels converge together. Each pixel is the mean of its own samples, the
+/// pixels sampled one more time than the others by the last pass being as
+/// bright, and the pixels without samples being black.
+pub fn render_cancellable(
+    scene: &Scene,
+    settings: &RenderSettings,
+    cancellation: &CancellationToken,
+) -> Framebuffer {
+    let mut renderer =
+        ProgressiveRenderer::new(scene, settings).with_cancellation(cancellation.clone());
+    while !renderer.is_finished() && !cancellation.is_cancelled() {
+        renderer.render_pass(1);
+    }
+
+    renderer.framebuffer()
+}
+
+/// Renders the scene in passes of `pass_samples` samples per pixel, calling
+/// `on_pass` with the number of the pass, from one, and the image rendered so
+/// far after each of them.
+///
+/// The render stops when `on_pass` breaks, and otherwise gives the same image
+/// as [`render`].
+///
+/// # Panics
+///
+/// If `pass_samples` is zero.
+pub fn render_progressive(
+    scene: &Scene,
+    settings: &RenderSettings,
+    pass_samples: u32,
+    mut on_pass: impl FnMut(u32, &Framebuffer) -> ControlFlow<()>,
+) -> Framebuffer {
+    assert!(pass_samples > 0, "A pass should take samples");
+    let mut renderer = ProgressiveRenderer::new(scene, settings);
+
+    for pass in 1.. {
+        renderer.render_pass(pass_samples);
+        let framebuffer = renderer.framebuffer();
+        if on_pass(pass, &framebuffer).is_break() || renderer.is_finished() {
+            return framebuffer;
+        }
+    }
+    unreachable!("The passes should finish the render")
+}
+
+/// Samples of a pixel accumulated over the passes of a
+/// [`ProgressiveRenderer`].
+#[derive(Debug, Clone, Default)]
+struct PixelAccumulator {
+    /// Statistics of the samples taken in the pixel, whose radiance is
+    /// splatted to the film.
+    stats: LuminanceStats,
+    aovs: AovSum,
+}
+
+impl PixelAccumulator {
+    /// Number of bytes written by [`PixelAccumulator::write_checkpoint`].
+    const CHECKPOINT_LEN: usize = 17 * size_of::<u32>();
+
+    fn is_finished(&self, settings: &RenderSettings) -> bool {
+        let count = self.stats.count;
+        count >= settings.samples_per_pixel
+            || settings.adaptive_sampling.is_some_and(|adaptive| {
+                count >= adaptive.min_samples && self.stats.relative_error() < adaptive.threshold
+            })
+    }
+
+    fn write_checkpoint(
+        &self,
+        writer: &mut CheckpointWriter<impl Write>,
+    ) -> Result<(), CheckpointError> {
+        let (stats, aovs) = (&self.stats, &self.aovs);
+        writer.u32(stats.count)?;
+        writer.f32(stats.mean)?;
+        writer.f32(stats.m2)?;
+        writer.u32(aovs.count)?;
+        writer.color(aovs.albedo)?;
+        writer.vec3(aovs.normal)?;
+        writer.color(aovs.emission)?;
+        writer.f32(aovs.depth)?;
+        writer.u32(aovs.hits)?;
+        // The object of the first sample can be any identifier.
+        writer.u32(aovs.object_id.is_some() as u32)?;
+        writer.u32(aovs.object_id.unwrap_or_default())
+    }
+
+    fn read_checkpoint(reader: &mut CheckpointReader<impl Read>) -> Result<Self, CheckpointError> {
+        let stats = LuminanceStats {
+            count: reader.u32()?,
+            mean: reader.f32()?,
+            m2: reader.f32()?,
+        };
+        let aovs = AovSum {
+            count: reader.u32()?,
+            albedo: reader.color()?,
+            normal: reader.vec3()?,
+            emission: reader.color()?,
+            depth: reader.f32()?,
+            hits: reader.u32()?,
+            object_id: {
+                let first_sampled = reader.u32()? != 0;
+                let object_id = reader.u32()?;
+                first_sampled.then_some(object_id)
+            },
+        };
+
+        Ok(Self { stats, aovs })
+    }
+}
+
+/// Pixels of a [`Tile`] with their samples.
+#[derive(Debug, Clone)]
+struct TileAccumulator {
+    tile: Tile,
+    /// Row by row from the top left corner of the tile.
+    pixels: Vec<PixelAccumulator>,
+    /// Samples of the pixels of the tile, splatted across its edges.
+    film: FilmTile,
+}
+
+impl TileAccumulator {
+    /// Takes up to `samples` more samples in each pixel of the tile, until
+    /// `cancellation` is cancelled.
+    fn render(
+        &mut self,
+        samples: u32,
+        (scene, camera, lights, settings): (&Scene, &Camera, &Lights, &RenderSettings),
+        cancellation: &CancellationToken,
+    ) {
+        zone!();
+        for index in 0..self.pixels.len() {
+            if cancellation.is_cancelled() {
+                return;
+            }
+            for _ in 0..samples {
+                if self.pixels[index].is_finished(settings) {
+                    break;
+                }
+                sample_pixel(self, index, scene, camera, lights, settings);
+            }
+        }
+    }
+
+    fn checkpoint_len(&self) -> usize {
+        self.film.checkpoint_len() + self.pixels.len() * PixelAccumulator::CHECKPOINT_LEN
+    }
+
+    fn write_checkpoint(
+        &self,
+        writer: &mut CheckpointWriter<impl Write>,
+    ) -> Result<(), CheckpointError> {
+        self.film.write_checkpoint(writer)?;
+        for pixel in &self.pixels {
+            pixel.write_checkpoint(writer)?;
+        }
+
+        Ok(())
+    }
+
+    fn read_checkpoint(
+        &mut self,
+        reader: &mut CheckpointReader<impl Read>,
+    ) -> Result<(), CheckpointError> {
+        self.film.read_checkpoint(reader)?;
+        for pixel in &mut self.pixels {
+            *pixel = PixelAccumulator::read_checkpoint(reader)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Render whose samples are taken in successive passes over the image.
+///
+/// Each pass hands the tiles to the render threads in the order of the
+/// settings, the idle threads taking the next tile. Each sample has its own
+/// random stream, so the image does not depend on the tiles, and with the box
+/// filter nor on how the samples are split into passes.
+pub struct ProgressiveRenderer<'a> {
+    scene: &'a Scene,
+    settings: RenderSettings,
+    camera: Camera,
+    lights: &'a Lights,
+    tiles: Vec<TileAccumulator>,
+    cancellation: CancellationToken,
+}
+
+impl<'a> ProgressiveRenderer<'a> {
+    /// Prepares a render without samples, the aspect ratio of the scene
+    /// camera being replaced by the one of the image.
+    pub fn new(scene: &'a Scene, settings: &RenderSettings) -> Self {
+        let (width, height) = (settings.image_width, settings.image_height);
+        let film = Film::new(width, height, settings.filter);
+        let tiles = tiles(width, height, settings.tile_size, settings.tile_order)
+            .into_iter()
+            .map(|tile| TileAccumulator {
+                tile,
+                film: film.tile(&tile),
+                pixels: vec![PixelAccumulator::default(); tile.pixel_count()],
+            })
+            .collect();
+
+        Self {
+            scene,
+            settings: settings.clone(),
+            camera: scene.camera().with_aspect_ratio(settings.aspect_ratio()),
+            lights: if settings.sample_lights {
+                scene.lights()
+            } else {
+                &NO_LIGHTS
+            },
+            tiles,
+            cancellation: CancellationToken::new(),
+        }
+    }
+
+    /// Stops the passes when `cancellation` is cancelled, leaving the pixels
+    /// with the samples taken so far.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// Takes up to `samples` more samples in each pixel, which are not
+    /// sampled beyond `samples_per_pixel` nor once converged.
+    pub fn render_pass(&mut self, samples: u32) {
+        let context = (self.scene, &self.camera, self.lights, &self.settings);
+        let cancellation = &self.cancellation;
+
+        // Bridging keeps the order of the tiles, rayon balancing the load.
+        self.tiles
+            .iter_mut()
+            .par_bridge()
+            .for_each(|tile| tile.render(samples, context, cancellation));
+    }
+
+    /// Number of tiles of the image, indexed in the order of the settings.
+    pub fn tile_count(&self) -> usize {
+        self.tiles.len()
+    }
+
+    /// Takes the remaining samples of the tiles at `indices`, which are
+    /// rendered in parallel.
+    ///
+    /// # Panics
+    ///
+    /// If an index is not below [`ProgressiveRenderer::tile_count`].
+    pub fn render_tiles(&mut self, indices: &[usize]) {
+        let context = (self.scene, &self.camera, self.lights, &self.settings);
+        let mut selected = vec![false; self.tiles.len()];
+        for &index in indices {
+            selected[index] = true;
+        }
+
+        self.tiles
+            .iter_mut()
+            .zip(selected)
+            .filter_map(|(tile, selected)| selected.then_some(tile))
+            .par_bridge()
+            .for_each(|tile| tile.render(u32::MAX, context, &CancellationToken::new()));
+    }
+
+    /// Writes the samples of the tile at `index`, which
+    /// [`ProgressiveRenderer::read_tile`] reads back.
+    pub(crate) fn write_tile(
+        &self,
+        index: usize,
+        writer: &mut CheckpointWriter<impl Write>,
+    ) -> Result<(), CheckpointError> {
+        self.tiles[index].write_checkpoint(writer)
+    }
+
+    /// Number of bytes written by [`ProgressiveRenderer::write_tile`] for the
+    /// tile at `index`.
+    pub(crate) fn tile_len(&self, index: usize) -> usize {
+        self.tiles[index].checkpoint_len()
+    }
+
+    /// Replaces the samples of the tile at `index`, which is left unchanged
+    /// if they can not be read.
+    pub(crate) fn read_tile(
+        &mut self,
+        index: usize,
+        reader: &mut CheckpointReader<impl Read>,
+    ) -> Result<(), CheckpointError> {
+        let mut tile = self.tiles[index].clone();
+        tile.read_checkpoint(reader)?;
+        self.tiles[index] = tile;
+
+        Ok(())
+    }
+
+    /// Continues the render of the same scene saved by
+    /// [`ProgressiveRenderer::save_checkpoint`], with the same settings but
+    /// for `samples_per_pixel`.
+    ///
+    /// The render continued with the same passes gives the same image as if
+    /// it had not been interrupted.
+    pub fn resume(
+        scene: &'a Scene,
+        settings: &RenderSettings,
+        path: &Path,
+    ) -> Result<Self, CheckpointError> {
+        let mut renderer = Self::new(scene, settings);
+        let mut reader = CheckpointReader::open(path)?;
+        if reader.bytes()? != checkpoint_key(scene, settings)?.as_bytes() {
+            return Err(CheckpointError::OtherSettings);
+        }
+        for tile in &mut renderer.tiles {
+            tile.read_checkpoint(&mut reader)?;
+        }
+        reader.finish()?;
+
+        Ok(renderer)
+    }
+
+    /// Saves the samples taken so far to `path`.
+    ///
+    /// The checkpoint is written to a `.partial` file renamed once complete,
+    /// so that the previous checkpoint is kept if the render stops while
+    /// writing.
+    pub fn save_checkpoint(&self, path: &Path) -> Result<(), CheckpointError> {
+        let mut partial_path = path.as_os_str().to_owned();
+        partial_path.push(".partial");
+        let mut writer = CheckpointWriter::create(Path::new(&partial_path))?;
+
+        writer.bytes(checkpoint_key(self.scene, &self.settings)?.as_bytes())?;
+        for tile in &self.tiles {
+            tile.write_checkpoint(&mut writer)?;
+        }
+        writer.finish()?;
+
+        Ok(std::fs::rename(partial_path, path)?)
+    }
+
+    /// Whether every pixel reached `samples_per_pixel` or converged.
+    pub fn is_finished(&self) -> bool {
+        self.pixels()
+            .all(|(_, pixel)| pixel.is_finished(&self.settings))
+    }
+
+    /// Gets the image of the samples taken so far.
+    pub fn framebuffer(&self) -> Framebuffer {
+        let (width, height) = (self.settings.image_width, self.settings.image_height);
+        let mut film = Film::new(width, height, self.settings.filter);
+        // The tiles are merged in a fixed order, so that the sums do not
+        // depend on the threads.
+        for tile in &self.tiles {
+            film.merge_tile(&tile.film);
+        }
+
+        let mut sample_counts = vec![0; width * height];
+        let mut aovs = vec![AovPixel::default(); width * height];
+        for ((x, y), pixel) in self.pixels() {
+            sample_counts[y * width + x] = pixel.stats.count;
+            aovs[y * width + x] = pixel.aovs.average();
+        }
+
+        let framebuffer = Framebuffer::from_pixels(width, height, film.pixels())
+            .with_sample_counts(sample_counts);
+        if self.settings.aovs {
+            framebuffer.with_aovs(Aovs::from_pixels(width, height, aovs))
+        } else {
+            framebuffer
+        }
+    }
+
+    /// Iterates over the pixels with their position from the top left corner.
+    fn pixels(&self) -> impl Iterator<Item = ((usize, usize), &PixelAccumulator)> {
+        self.tiles.iter().flat_map(|tile| {
+            tile.pixels
+                .iter()
+                .enumerate()
+                .map(|(index, pixel)| (tile.tile.pixel_position(index), pixel))
         })
-        .collect::<Vec<u8>>()
+    }
+}
+
+/// Describes the scene and the settings which should not change when resuming
+/// a render from a checkpoint.
+///
+/// The scenes that can not be described, with noise textures which were not
+/// created from a seed, could not be told apart and are not checkpointed.
+fn checkpoint_key(scene: &Scene, settings: &RenderSettings) -> Result<String, CheckpointError> {
+    let settings = RenderSettings {
+        samples_per_pixel: 0,
+        ..settings.clone()
+    };
+    let description = scene
+        .to_description()
+        .and_then(|description| description.to_toml_string())
+        .map_err(CheckpointError::UndescribableScene)?;
+
+    Ok(format!(
+        "{settings:?} scene {:016x}",
+        fingerprint(description.as_bytes())
+    ))
+}
+
+/// Adds a sample of the pixel at `index` in the tile to its film.
+fn sample_pixel(
+    tile: &mut TileAccumulator,
+    index: usize,
+    scene: &Scene,
+    camera: &Camera,
+    lights: &Lights,
+    settings: &RenderSettings,
+) {
+    let (image_width, image_height) = (settings.image_width, settings.image_height);
+    // Column and row from the bottom of the image.
+    let (i, y) = tile.tile.pixel_position(index);
+    let j = image_height - 1 - y;
+    let pixel = &mut tile.pixels[index];
+    let sample_index = pixel.stats.count;
+    let pixel_index = j * image_width + i;
+    let rng = &mut sample_rng(settings.seed, pixel_index, sample_index);
+    let context = (scene, camera, lights, settings);
+    let (jitter, sample, aov) = match settings.sampler {
+        SamplerKind::Independent => take_sample(&mut IndependentSampler::new(rng), (i, j), context),
+        SamplerKind::Stratified => {
+            let sampler = &mut PointSetSampler::new_stratified(
+                rng,
+                settings.seed,
+                pixel_index,
+                sample_index,
+                settings.samples_per_pixel,
+            );
+            take_sample(sampler, (i, j), context)
+        }
+        SamplerKind::Sobol => {
+            let sampler =
+                &mut PointSetSampler::new_sobol(rng, settings.seed, pixel_index, sample_index);
+            take_sample(sampler, (i, j), context)
+        }
+        SamplerKind::BlueNoise => {
+            let sampler =
+                &mut PointSetSampler::new_blue_noise(rng, settings.seed, (i, j), sample_index);
+            take_sample(sampler, (i, j), context)
+        }
+    };
+    if let Some(aov) = aov {
+        pixel.aovs.add(&aov);
+    }
+
+    // The rows of the film go down from the top of the image.
+    tile.film
+        .add_sample((i, y), (jitter.0, 1.0 - jitter.1), sample);
+    pixel.stats.add(sample.luminance());
+}
+
+/// Traces a sample of the pixel at column `i` and row `j` from the bottom of
+/// the image, giving its offset in the pixel, its radiance and the AOVs of
+/// its first hit when enabled.
+fn take_sample(
+    sampler: &mut impl Sampler,
+    (i, j): (usize, usize),
+    (scene, camera, lights, settings): (&Scene, &Camera, &Lights, &RenderSettings),
+) -> ((f32, f32), Color, Option<AovPixel>) {
+    sampler.start_dimensions(PIXEL_DIMENSIONS);
+    let (jitter_x, jitter_y) = (sampler.gen::<f32>(), sampler.gen::<f32>());
+    let u = (i as f32 + jitter_x) / (settings.image_width as f32 - 1.0);
+    let v = (j as f32 + jitter_y) / (settings.image_height as f32 - 1.0);
+    let ray = camera.get_ray(u, v, sampler);
+    let aov = settings
+        .aovs
+        .then(|| first_hit_aov(&ray, scene.hittable_list()));
+
+    let mut sample = ray_color(
+        ray,
+        scene.background_color(),
+        scene.hittable_list(),
+        lights,
+        settings.max_depth,
+        settings.russian_roulette_depth,
+        sampler,
+    );
+    if let Some(clamp) = settings.clamp {
+        sample = sample.clamp(0.0, clamp);
+    }
+
+    ((jitter_x, jitter_y), sample, aov)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aov::NO_OBJECT;
+    use crate::camera::Camera;
+    use crate::cancellation::CancellationToken;
+    use crate::checkpoint::CheckpointError;
+    use crate::film::Filter;
+    use crate::framebuffer::Framebuffer;
+    use crate::geometry::hittable_world::HittableWorld;
+    use crate::geometry::sphere::Sphere;
+    use crate::geometry::xz_rectangle::XzRectangle;
+    use crate::light::Lights;
+    use crate::material::Material;
+    use crate::math::color::Color;
+    use crate::math::perlin::Perlin;
+    use crate::ray::Ray;
+    use crate::renderer::{
+        ray_color, render, render_cancellable, render_progressive, AdaptiveSampling,
+        ProgressiveRenderer, RenderSettings,
+    };
+    use crate::sampler::{IndependentSampler, SamplerKind};
+    use crate::scene::Scene;
+    use crate::texture::Texture;
+    use crate::tile::TileOrder;
+    use glam::Vec3A;
+    use rand_xoshiro::rand_core::SeedableRng;
+    use std::ops::ControlFlow;
+    use std::time::Duration;
+
+    #[test]
+    fn render_uses_settings_resolution() {
+        let scene = Scene::bench_three_spheres();
+        let settings = RenderSettings {
+            samples_per_pixel: 1,
+            max_depth: 4,
+            ..RenderSettings::new(16, 9)
+        };
+
+        let framebuffer = render(&scene, &settings);
+
+        assert_eq!(framebuffer.width(), 16);
+        assert_eq!(framebuffer.height(), 9);
+        assert_eq!(framebuffer.pixels().len(), 16 * 9);
+    }
+
+    #[test]
+    fn render_is_reproducible_across_thread_counts() {
+        let scene = Scene::bench_three_spheres();
+        let settings = RenderSettings {
+            samples_per_pixel: 2,
+            max_depth: 4,
+            seed: 42,
+            ..RenderSettings::new(24, 16)
+        };
+        let render_with_threads = |threads| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap()
+                .install(|| render(&scene, &settings))
+        };
+
+        let single_thread = render_with_threads(1);
+
+        assert_eq!(single_thread, render_with_threads(4));
+        assert_eq!(single_thread, render(&scene, &settings));
+    }
+
+    #[test]
+    fn render_aovs_of_first_hits() {
+        let scene = Scene::bench_three_spheres();
+        let settings = RenderSettings {
+            samples_per_pixel: 1,
+            max_depth: 4,
+            aovs: true,
+            ..RenderSettings::new(48, 27)
+        };
+
+        let framebuffer = render(&scene, &settings);
+        let aovs = framebuffer.aovs().unwrap();
+
+        assert_eq!((aovs.width(), aovs.height()), (48, 27));
+        let metal = aovs
+            .pixels()
+            .iter()
+            .find(|pixel| pixel.object_id == 1)
+            .unwrap();
+        assert_eq!(metal.albedo, Color::new(0.7, 0.6, 0.5));
+        for pixel in aovs.pixels() {
+            if pixel.object_id == NO_OBJECT {
+                assert!(pixel.depth.is_infinite() && pixel.normal == Vec3A::ZERO);
+            } else {
+                assert!(pixel.object_id < 3 && pixel.depth.is_finite());
+                assert!((pixel.normal.length() - 1.0).abs() < 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn render_does_not_depend_on_tiles() {
+        let scene = Scene::bench_three_spheres();
+        let settings = RenderSettings {
+            samples_per_pixel: 2,
+            max_depth: 4,
+            ..RenderSettings::new(40, 24)
+        };
+        let render_with_tiles = |tile_size, tile_order| {
+            let settings = RenderSettings {
+                tile_size,
+                tile_order,
+                ..settings.clone()
+            };
+            render(&scene, &settings)
+        };
+
+        let scanlines = render_with_tiles(40, TileOrder::Scanline);
+
+        assert_eq!(scanlines, render_with_tiles(7, TileOrder::Spiral));
+        assert_eq!(scanlines, render_with_tiles(16, TileOrder::Hilbert));
+    }
+
+    #[test]
+    fn samplers_agree_on_the_mean_of_the_image() {
+        let scene = Scene::bench_three_spheres();
+        let mean_luminance = |sampler| {
+            let settings = RenderSettings {
+                samples_per_pixel: 16,
+                max_depth: 4,
+                sampler,
+                ..RenderSettings::new(32, 18)
+            };
+            let framebuffer = render(&scene, &settings);
+            let pixels = framebuffer.pixels();
+            pixels.iter().map(|pixel| pixel.luminance()).sum::<f32>() / pixels.len() as f32
+        };
+
+        let independent = mean_luminance(SamplerKind::Independent);
+        for sampler in [
+            SamplerKind::Stratified,
+            SamplerKind::Sobol,
+            SamplerKind::BlueNoise,
+        ] {
+            let mean = mean_luminance(sampler);
+            assert!(
+                (mean - independent).abs() < 0.02 * independent,
+                "{sampler:?}: {mean}"
+            );
+        }
+    }
+
+    #[test]
+    fn adaptive_sampling_stops_on_converged_pixels() {
+        let scene = Scene::bench_three_spheres();
+        let settings = RenderSettings {
+            samples_per_pixel: 64,
+            max_depth: 4,
+            adaptive_sampling: Some(AdaptiveSampling {
+                min_samples: 8,
+                threshold: 0.01,
+            }),
+            ..RenderSettings::new(32, 18)
+        };
+
+        let framebuffer = render(&scene, &settings);
+        let counts = framebuffer.sample_counts().unwrap();
+
+        // The sky has a constant color, the spheres are noisy.
+        assert_eq!(counts.iter().min(), Some(&8));
+        assert_eq!(counts.iter().max(), Some(&64));
+    }
+
+    #[test]
+    fn progressive_render_matches_render() {
+        let scene = Scene::bench_three_spheres();
+        let settings = RenderSettings {
+            samples_per_pixel: 8,
+            max_depth: 4,
+            aovs: true,
+            ..RenderSettings::new(24, 16)
+        };
+
+        let mut passes = 0;
+        let progressive = render_progressive(&scene, &settings, 3, |pass, _| {
+            passes = pass;
+            ControlFlow::Continue(())
+        });
+        let stopped = render_progressive(&scene, &settings, 3, |_, _| ControlFlow::Break(()));
+
+        assert_eq!(passes, 3);
+        assert_eq!(progressive, render(&scene, &settings));
+        assert!(stopped
+            .sample_counts()
+            .unwrap()
+            .iter()
+            .all(|&count| count == 3));
+    }
+
+    #[test]
+    fn cancelled_render_keeps_the_samples_taken() {
+        let scene = Scene::bench_three_spheres();
+        let settings = RenderSettings {
+            samples_per_pixel: 32,
+            max_depth: 4,
+            ..RenderSettings::new(24, 16)
+        };
+        let mean_luminance = |framebuffer: &Framebuffer| {
+            let pixels = framebuffer.pixels();
+            pixels.iter().map(|pixel| pixel.luminance()).sum::<f32>() / pixels.len() as f32
+        };
+
+        // Cancelled after a fixed number of passes, so that the samples taken
+        // do not depend on the speed of the machine.
+        let cancellation = CancellationToken::new();
+        let mut renderer =
+            ProgressiveRenderer::new(&scene, &settings).with_cancellation(cancellation.clone());
+        for _ in 0..16 {
+            renderer.render_pass(1);
+        }
+        cancellation.cancel();
+        renderer.render_pass(1);
+        let stopped = renderer.framebuffer();
+        let reference = render(
+            &scene,
+            &RenderSettings {
+                samples_per_pixel: 16,
+                ..settings.clone()
+            },
+        );
+
+        assert!(stopped
+            .sample_counts()
+            .unwrap()
+            .iter()
+            .all(|&count| count == 16));
+        assert!((mean_luminance(&stopped) / mean_luminance(&reference) - 1.0).abs() < 0.05);
+        let nothing = render_cancellable(&scene, &settings, &cancellation);
+        assert!(nothing
+            .sample_counts()
+            .unwrap()
+            .iter()
+            .all(|&count| count == 0));
+        let unbounded = CancellationToken::new().with_time_budget(Duration::MAX);
+        assert!(!unbounded.is_cancelled());
+    }
+
+    #[test]
+    fn resumed_render_matches_uninterrupted_render() {
+        let scene = Scene::bench_three_spheres();
+        let settings = RenderSettings {
+            samples_per_pixel: 8,
+            max_depth: 4,
+            aovs: true,
+            filter: Filter::Tent { radius: 1.0 },
+            ..RenderSettings::new(24, 16)
+        };
+        let path = std::env::temp_dir().join("raytracing_resumed_render.checkpoint");
+
+        let mut uninterrupted = ProgressiveRenderer::new(&scene, &settings);
+        while !uninterrupted.is_finished() {
+            uninterrupted.render_pass(3);
+        }
+        // Interrupted after the passes reaching 6 samples per pixel.
+        let interrupted_settings = RenderSettings {
+            samples_per_pixel: 6,
+            ..settings.clone()
+        };
+        let mut interrupted = ProgressiveRenderer::new(&scene, &interrupted_settings);
+        interrupted.render_pass(3);
+        interrupted.render_pass(3);
+        interrupted.save_checkpoint(&path).unwrap();
+        let mut resumed = ProgressiveRenderer::resume(&scene, &settings, &path).unwrap();
+        resumed.render_pass(3);
+        let other_settings = RenderSettings {
+            seed: 1,
+            ..settings.clone()
+        };
+        let other = ProgressiveRenderer::resume(&scene, &other_settings, &path);
+        let other_scene = Scene::two_spheres();
+        let other_scene = ProgressiveRenderer::resume(&other_scene, &settings, &path);
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &bytes[..bytes.len() / 2]).unwrap();
+        let truncated = ProgressiveRenderer::resume(&scene, &settings, &path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(resumed.is_finished());
+        assert_eq!(resumed.framebuffer(), uninterrupted.framebuffer());
+        assert!(matches!(other, Err(CheckpointError::OtherSettings)));
+        assert!(matches!(other_scene, Err(CheckpointError::OtherSettings)));
+        assert!(matches!(truncated, Err(CheckpointError::Truncated)));
+    }
+
+    #[test]
+    fn undescribable_scenes_are_not_checkpointed() {
+        let mut rng = rand_xoshiro::Xoshiro256Plus::seed_from_u64(1);
+        let mut noise_scene = |radius| {
+            let mut world = HittableWorld::new();
+            let texture = Texture::new_noise(Perlin::new(&mut rng), 4.0);
+            world.add_sphere(Sphere::new(
+                Vec3A::ZERO,
+                radius,
+                Material::new_lambertian(texture),
+            ));
+            world.init_bvh_nodes();
+            Scene::new(world, Camera::default(), Color::new(0.7, 0.8, 1.0))
+        };
+        let (small, big) = (noise_scene(1.0), noise_scene(2.0));
+        let settings = RenderSettings {
+            samples_per_pixel: 1,
+            ..RenderSettings::new(8, 8)
+        };
+        let path = std::env::temp_dir().join("raytracing_undescribable_scene.checkpoint");
+
+        let saved = ProgressiveRenderer::new(&small, &settings).save_checkpoint(&path);
+        let scene = Scene::bench_three_spheres();
+        ProgressiveRenderer::new(&scene, &settings)
+            .save_checkpoint(&path)
+            .unwrap();
+        let resumed_small = ProgressiveRenderer::resume(&small, &settings, &path);
+        let resumed_big = ProgressiveRenderer::resume(&big, &settings, &path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(saved, Err(CheckpointError::UndescribableScene(_))));
+        assert!(matches!(
+            resumed_small,
+            Err(CheckpointError::UndescribableScene(_))
+        ));
+        assert!(matches!(
+            resumed_big,
+            Err(CheckpointError::UndescribableScene(_))
+        ));
+    }
+
+    #[test]
+    fn light_sampling_matches_bouncing() {
+        let floors = [
+            Material::new_lambertian_color(Color::new(0.5, 0.5, 0.5)),
+            Material::new_metal(Color::new(0.5, 0.5, 0.5), 0.3),
+        ];
+        for floor in floors {
+            let mut world = HittableWorld::new();
+            world.add_xz_rectangle(XzRectangle::new(floor, -10.0, 10.0, -10.0, 10.0, 0.0));
+            let light = Material::new_diffuse_light_color(Color::new(4.0, 4.0, 4.0));
+            world.add_xz_rectangle(XzRectangle::new(light.clone(), -1.0, 1.0, -1.0, 1.0, 2.0));
+            world.add_sphere(Sphere::new(Vec3A::new(-2.0, 1.0, 0.0), 0.3, light));
+            world.init_bvh_nodes();
+            let lights = Lights::new(&world);
+
+            let mut rng = rand_xoshiro::Xoshiro256Plus::seed_from_u64(7);
+            let mut sampler = IndependentSampler::new(&mut rng);
+            let ray = Ray::new(Vec3A::new(0.5, 1.0, 0.0), Vec3A::new(0.0, -1.0, 0.0));
+            let mut mean_color = |lights: &Lights| {
+                let samples = 40_000;
+                (0..samples)
+                    .map(|_| ray_color(ray, &Color::black(), &world, lights, 2, 2, &mut sampler)[0])
+                    .sum::<f32>()
+                    / samples as f32
+            };
+
+            let bouncing = mean_color(&Lights::default());
+            let sampled = mean_color(&lights);
+
+            assert!(bouncing > 0.1);
+            assert!((bouncing - sampled).abs() < 0.03 * bouncing);
+        }
+    }
+
+    #[test]
+    fn russian_roulette_keeps_the_mean() {
+        let mut world = HittableWorld::new();
+        let walls = Material::new_lambertian_color(Color::new(0.8, 0.8, 0.8));
+        world.add_sphere(Sphere::new(Vec3A::ZERO, 10.0, walls));
+        let light = Material::new_diffuse_light_color(Color::new(4.0, 4.0, 4.0));
+        world.add_sphere(Sphere::new(Vec3A::new(0.0, 5.0, 0.0), 1.0, light));
+        world.init_bvh_nodes();
+        let lights = Lights::new(&world);
+
+        let mut rng = rand_xoshiro::Xoshiro256Plus::seed_from_u64(3);
+        let mut sampler = IndependentSampler::new(&mut rng);
+        let ray = Ray::new(Vec3A::ZERO, Vec3A::new(0.3, -1.0, 0.2));
+        let mut mean_color = |russian_roulette_depth| {
+            let samples = 20_000;
+            (0..samples)
+                .map(|_| {
+                    ray_color(
+                        ray,
+                        &Color::black(),
+                        &world,
+                        &lights,
+                        8,
+                        russian_roulette_depth,
+                        &mut sampler,
+                    )[0]
+                })
+                .sum::<f32>()
+                / samples as f32
+        };
+
+        let full_paths = mean_color(8);
+        let terminated_paths = mean_color(1);
+
+        assert!((full_paths - terminated_paths).abs() < 0.02 * full_paths);
+    }
 }
@@ -0,0 +1,514 @@
+use clap::ValueEnum;
+use rand::Rng;
+use rand_xoshiro::rand_core::{impls, Error, RngCore, SeedableRng};
+use rand_xoshiro::Xoshiro256Plus;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// Dimensions of the jitter of the sample in its pixel.
+pub const PIXEL_DIMENSIONS: (u32, u32) = (0, 2);
+/// Dimensions of the point sampled on the lens of the camera.
+pub const LENS_DIMENSIONS: (u32, u32) = (2, 2);
+/// Dimension of the time of the camera ray.
+pub const TIME_DIMENSIONS: (u32, u32) = (4, 1);
+/// First dimension of the bounces, each having [`DIMENSIONS_PER_BOUNCE`].
+pub const BOUNCE_DIMENSION: u32 = 6;
+/// Dimensions of the scattering, of the Russian roulette and of the light
+/// sampling of a bounce, in this order, the pairs of points starting on even
+/// dimensions.
+pub const DIMENSIONS_PER_BOUNCE: u32 = 8;
+
+/// Dimensions of the scattering of the bounce `depth`.
+pub fn scattering_dimensions(depth: u32) -> (u32, u32) {
+    (BOUNCE_DIMENSION + depth * DIMENSIONS_PER_BOUNCE, 4)
+}
+
+/// Dimension of the Russian roulette of the bounce `depth`.
+pub fn roulette_dimensions(depth: u32) -> (u32, u32) {
+    (BOUNCE_DIMENSION + depth * DIMENSIONS_PER_BOUNCE + 4, 1)
+}
+
+/// Dimensions of the light sampled at the bounce `depth`: the choice of the
+/// light, then the point sampled on it.
+pub fn light_dimensions(depth: u32) -> (u32, u32) {
+    (BOUNCE_DIMENSION + depth * DIMENSIONS_PER_BOUNCE + 5, 3)
+}
+
+/// Source of the random numbers of one sample of a pixel.
+///
+/// The numbers drawn are the successive dimensions of the sample, which a
+/// sampler can distribute better than independent numbers across the samples
+/// of a pixel. Since the rejection sampling of the materials draws a varying
+/// count of numbers, each part of a path moves to its own dimensions with
+/// [`Sampler::start_dimensions`].
+pub trait Sampler: RngCore {
+    /// Moves to the first of `(first, count)` dimensions, the numbers drawn
+    /// beyond them being independent.
+    fn start_dimensions(&mut self, dimensions: (u32, u32));
+}
+
+/// Kind of the [`Sampler`] of the pixels.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+pub enum SamplerKind {
+    /// Independent uniform numbers.
+    Independent,
+    /// Jittered on a grid of about `samples_per_pixel` strata per pair of
+    /// dimensions, visited in a random order.
+    Stratified,
+    /// Owen-scrambled Sobol points, shuffled per pair of dimensions.
+    Sobol,
+    /// Sobol points rotated by a blue noise mask, which spreads the error of
+    /// neighbouring pixels as high frequency noise.
+    BlueNoise,
+}
+
+/// Sampler drawing independent numbers from the stream of the pixel.
+pub struct IndependentSampler<'a> {
+    rng: &'a mut Xoshiro256Plus,
+}
+
+impl<'a> IndependentSampler<'a> {
+    pub fn new(rng: &'a mut Xoshiro256Plus) -> Self {
+        Self { rng }
+    }
+}
+
+impl Sampler for IndependentSampler<'_> {
+    fn start_dimensions(&mut self, _dimensions: (u32, u32)) {}
+}
+
+impl RngCore for IndependentSampler<'_> {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+/// Low discrepancy point set, giving a dimension of a sample as the bits of a
+/// fraction.
+enum PointSet {
+    Stratified { columns: u32, rows: u32 },
+    Sobol,
+    BlueNoise { x: usize, y: usize },
+}
+
+/// Sampler taking the dimensions of its sample from a [`PointSet`], and the
+/// numbers beyond them from the stream of the pixel.
+pub struct PointSetSampler<'a> {
+    rng: &'a mut Xoshiro256Plus,
+    points: PointSet,
+    index: u32,
+    seed: u64,
+    dimension: u32,
+    end: u32,
+    cached_pair: Option<(u32, (u32, u32))>,
+}
+
+impl<'a> PointSetSampler<'a> {
+    /// Creates the sampler of the sample `index` of the pixel at
+    /// `pixel_index`, which should be below `samples_per_pixel` to be
+    /// stratified.
+    pub fn new_stratified(
+        rng: &'a mut Xoshiro256Plus,
+        seed: u64,
+        pixel_index: usize,
+        index: u32,
+        samples_per_pixel: u32,
+    ) -> Self {
+        let columns = (samples_per_pixel as f32).sqrt().ceil().max(1.0) as u32;
+        let rows = samples_per_pixel.div_ceil(columns).max(1);
+        let seed = hash(seed, pixel_index as u64) as u64;
+        Self::new(rng, PointSet::Stratified { columns, rows }, seed, index)
+    }
+
+    /// Creates the sampler of the sample `index` of the pixel at
+    /// `pixel_index`, whose points are scrambled independently of the other
+    /// pixels.
+    pub fn new_sobol(
+        rng: &'a mut Xoshiro256Plus,
+        seed: u64,
+        pixel_index: usize,
+        index: u32,
+    ) -> Self {
+        let seed = hash(seed, pixel_index as u64) as u64;
+        Self::new(rng, PointSet::Sobol, seed, index)
+    }
+
+    /// Creates the sampler of the sample `index` of the pixel at `x`, `y`,
+    /// whose points are the ones of every pixel rotated by the mask.
+    pub fn new_blue_noise(
+        rng: &'a mut Xoshiro256Plus,
+        seed: u64,
+        (x, y): (usize, usize),
+        index: u32,
+    ) -> Self {
+        Self::new(rng, PointSet::BlueNoise { x, y }, seed, index)
+    }
+
+    fn new(rng: &'a mut Xoshiro256Plus, points: PointSet, seed: u64, index: u32) -> Self {
+        Self {
+            rng,
+            points,
+            index,
+            seed,
+            dimension: 0,
+            end: 0,
+            cached_pair: None,
+        }
+    }
+
+    /// Gets the dimensions `2 * pair` and `2 * pair + 1`, which are
+    /// stratified together.
+    fn pair(&mut self, pair: u32) -> (u32, u32) {
+        if let Some((cached, values)) = self.cached_pair {
+            if cached == pair {
+                return values;
+            }
+        }
+        let pair_seed = hash(self.seed, pair as u64);
+
+        let values = match self.points {
+            PointSet::Stratified { columns, rows } => {
+                let stratum = permute(self.index % (columns * rows), columns * rows, pair_seed);
+                let jitter = |rng: &mut Xoshiro256Plus, cell, count| {
+                    let value = (cell as f32 + rng.gen::<f32>()) / count as f32;
+                    (value as f64 * 4_294_967_296.0).min(u32::MAX as f64) as u32
+                };
+                (
+                    jitter(self.rng, stratum % columns, columns),
+                    jitter(self.rng, stratum / columns, rows),
+                )
+            }
+            PointSet::Sobol => {
+                let index = nested_uniform_scramble(self.index, pair_seed);
+                (
+                    nested_uniform_scramble(sobol(index, 0), hash(pair_seed as u64, 0)),
+                    nested_uniform_scramble(sobol(index, 1), hash(pair_seed as u64, 1)),
+                )
+            }
+            PointSet::BlueNoise { x, y } => {
+                // Each pair is rotated by the mask shifted by a random offset,
+                // so that the pairs are not correlated.
+                let index = nested_uniform_scramble(self.index, pair_seed);
+                let mask = blue_noise_mask();
+                let rotation = |axis: u64| {
+                    let shift = hash(pair_seed as u64, axis) as usize;
+                    let mask_x = (x + shift) % BLUE_NOISE_SIZE;
+                    let mask_y = (y + (shift >> 16)) % BLUE_NOISE_SIZE;
+                    mask[mask_y * BLUE_NOISE_SIZE + mask_x]
+                };
+                (
+                    sobol(index, 0).wrapping_add(rotation(0)),
+                    sobol(index, 1).wrapping_add(rotation(1)),
+                )
+            }
+        };
+        self.cached_pair = Some((pair, values));
+
+        values
+    }
+}
+
+impl Sampler for PointSetSampler<'_> {
+    fn start_dimensions(&mut self, (first, count): (u32, u32)) {
+        self.dimension = first;
+        self.end = first + count;
+    }
+}
+
+impl RngCore for PointSetSampler<'_> {
+    fn next_u32(&mut self) -> u32 {
+        if self.dimension >= self.end {
+            return self.rng.next_u32();
+        }
+
+        let (x, y) = self.pair(self.dimension / 2);
+        self.dimension += 1;
+        if self.dimension % 2 == 1 {
+            x
+        } else {
+            y
+        }
+    }
+
+    /// The high bits, the ones converted to floats, are the next dimension.
+    fn next_u64(&mut self) -> u64 {
+        ((self.next_u32() as u64) << 32) | self.rng.next_u32() as u64
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        impls::fill_bytes_via_next(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// Mixes two numbers into a well distributed seed, with the finalizer of
+/// SplitMix64.
+fn hash(a: u64, b: u64) -> u32 {
+    let mut z = a ^ b.wrapping_add(1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (z ^ (z >> 31)) as u32
+}
+
+/// Second dimension of Sobol of the bytes of the index, for each byte, from
+/// the direction numbers of the primitive polynomial `x + 1`.
+const SOBOL_BYTES: [[u32; 256]; 4] = {
+    let mut directions = [0; 32];
+    let mut m: u32 = 1;
+    let mut k = 0;
+    while k < 32 {
+        directions[k] = m << (31 - k);
+        m ^= m << 1;
+        k += 1;
+    }
+
+    let mut bytes = [[0; 256]; 4];
+    let mut byte = 0;
+    while byte < 4 {
+        let mut value = 0;
+        while value < 256 {
+            let mut bit = 0;
+            while bit < 8 {
+                if value & (1 << bit) != 0 {
+                    bytes[byte][value] ^= directions[byte * 8 + bit];
+                }
+                bit += 1;
+            }
+            value += 1;
+        }
+        byte += 1;
+    }
+    bytes
+};
+
+/// Gets the `axis` dimension, zero or one, of the Sobol point `index`, the
+/// first being the bit reversal of the index.
+fn sobol(index: u32, axis: u32) -> u32 {
+    if axis == 0 {
+        return index.reverse_bits();
+    }
+
+    let [b0, b1, b2, b3] = index.to_le_bytes();
+    SOBOL_BYTES[0][b0 as usize]
+        ^ SOBOL_BYTES[1][b1 as usize]
+        ^ SOBOL_BYTES[2][b2 as usize]
+        ^ SOBOL_BYTES[3][b3 as usize]
+}
+
+/// Owen scrambling of the bits of a fraction with the hash of Laine and
+/// Karras improved by Burley, each bit being flipped depending on the more
+/// significant ones.
+fn nested_uniform_scramble(value: u32, seed: u32) -> u32 {
+    let mut x = value.reverse_bits().wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+
+    x.reverse_bits()
+}
+
+/// Random permutation of `[0, length[` selected by `seed`, from Kensler's
+/// correlated multi-jittered sampling.
+fn permute(mut i: u32, length: u32, seed: u32) -> u32 {
+    let mut mask = length - 1;
+    mask |= mask >> 1;
+    mask |= mask >> 2;
+    mask |= mask >> 4;
+    mask |= mask >> 8;
+    mask |= mask >> 16;
+
+    // Walks the cycles of a permutation of the next power of two until it is
+    // back in the range.
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= seed >> 16;
+        i ^= (i & mask) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= seed >> 23;
+        i ^= (i & mask) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & mask) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & mask) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & mask) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= mask;
+        i ^= i >> 5;
+        if i < length {
+            break (i + seed % length) % length;
+        }
+    }
+}
+
+const BLUE_NOISE_SIZE: usize = 64;
+
+/// Gets a tileable blue noise mask of 64x64 fractions, computed once with the
+/// void and cluster method.
+fn blue_noise_mask() -> &'static [u32] {
+    static MASK: OnceLock<Vec<u32>> = OnceLock::new();
+    MASK.get_or_init(void_and_cluster)
+}
+
+/// Ranks the pixels of the mask so that each prefix of the ranking is evenly
+/// spread, by adding the pixels in the largest voids of the previous ones.
+fn void_and_cluster() -> Vec<u32> {
+    const SIGMA: f32 = 1.5;
+    let size = BLUE_NOISE_SIZE;
+    let count = size * size;
+    let wrap = |d: usize| d.min(size - d) as f32;
+    let kernel: Vec<f32> = (0..count)
+        .map(|index| {
+            let (dx, dy) = (wrap(index % size), wrap(index / size));
+            (-(dx * dx + dy * dy) / (2.0 * SIGMA * SIGMA)).exp()
+        })
+        .collect();
+
+    let mut pattern = vec![false; count];
+    let mut energy = vec![0.0; count];
+    let toggle = |pattern: &mut [bool], energy: &mut [f32], index: usize| {
+        pattern[index] = !pattern[index];
+        let sign = if pattern[index] { 1.0 } else { -1.0 };
+        let (x, y) = (index % size, index / size);
+        for (other, energy) in energy.iter_mut().enumerate() {
+            let dx = (other % size + size - x) % size;
+            let dy = (other / size + size - y) % size;
+            *energy += sign * kernel[dy * size + dx];
+        }
+    };
+    let tightest_cluster = |pattern: &[bool], energy: &[f32]| {
+        (0..count)
+            .filter(|&index| pattern[index])
+            .max_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap()
+    };
+    let largest_void = |pattern: &[bool], energy: &[f32]| {
+        (0..count)
+            .filter(|&index| !pattern[index])
+            .min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap()
+    };
+
+    // Spreads a tenth of the pixels, moving the most clustered one to the
+    // largest void until it is already there.
+    let mut rng = Xoshiro256Plus::seed_from_u64(0);
+    let initial = count / 10;
+    while pattern.iter().filter(|&&set| set).count() < initial {
+        let index = rng.gen_range(0..count);
+        if !pattern[index] {
+            toggle(&mut pattern, &mut energy, index);
+        }
+    }
+    loop {
+        let cluster = tightest_cluster(&pattern, &energy);
+        toggle(&mut pattern, &mut energy, cluster);
+        let void = largest_void(&pattern, &energy);
+        toggle(&mut pattern, &mut energy, void);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut ranks = vec![0; count];
+    let (initial_pattern, initial_energy) = (pattern.clone(), energy.clone());
+    for rank in (0..initial).rev() {
+        let cluster = tightest_cluster(&pattern, &energy);
+        toggle(&mut pattern, &mut energy, cluster);
+        ranks[cluster] = rank;
+    }
+    let (mut pattern, mut energy) = (initial_pattern, initial_energy);
+    for rank in initial..count {
+        let void = largest_void(&pattern, &energy);
+        toggle(&mut pattern, &mut energy, void);
+        ranks[void] = rank;
+    }
+
+    // Centers each rank in its share of the fractions.
+    let step = (1u64 << 32) / count as u64;
+    ranks
+        .into_iter()
+        .map(|rank| (rank as u64 * step + step / 2) as u32)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sampler::{
+        blue_noise_mask, permute, PointSetSampler, Sampler, BLUE_NOISE_SIZE, PIXEL_DIMENSIONS,
+    };
+    use rand::Rng;
+    use rand_xoshiro::rand_core::SeedableRng;
+
+    #[test]
+    fn samplers_stratify_the_pixel() {
+        let mut rng = rand_xoshiro::Xoshiro256Plus::seed_from_u64(1);
+        for stratified in [true, false] {
+            let mut cells = [0; 16];
+            for index in 0..16 {
+                let mut sampler = if stratified {
+                    PointSetSampler::new_stratified(&mut rng, 9, 5, index, 16)
+                } else {
+                    PointSetSampler::new_sobol(&mut rng, 9, 5, index)
+                };
+                sampler.start_dimensions(PIXEL_DIMENSIONS);
+                let (x, y) = (sampler.gen::<f32>(), sampler.gen::<f32>());
+                cells[(y * 4.0) as usize * 4 + (x * 4.0) as usize] += 1;
+            }
+
+            assert_eq!(cells, [1; 16]);
+        }
+    }
+
+    #[test]
+    fn permute_is_a_permutation_for_any_seed() {
+        for length in [1, 9, 16] {
+            for seed in [0, 12345, u32::MAX] {
+                let mut values: Vec<_> = (0..length).map(|i| permute(i, length, seed)).collect();
+                values.sort_unstable();
+
+                assert_eq!(values, (0..length).collect::<Vec<_>>());
+            }
+        }
+    }
+
+    #[test]
+    fn blue_noise_mask_has_high_frequencies() {
+        let mask = blue_noise_mask();
+        let mut sorted = mask.to_vec();
+        sorted.sort_unstable();
+        sorted.dedup();
+
+        // The neighbours of white noise differ by a third on average.
+        let neighbour_difference = (0..mask.len())
+            .map(|index| {
+                let right =
+                    index / BLUE_NOISE_SIZE * BLUE_NOISE_SIZE + (index + 1) % BLUE_NOISE_SIZE;
+                (mask[index] as f64 - mask[right] as f64).abs() / u32::MAX as f64
+            })
+            .sum::<f64>()
+            / mask.len() as f64;
+
+        assert_eq!(sorted.len(), BLUE_NOISE_SIZE * BLUE_NOISE_SIZE);
+        assert!(neighbour_difference > 0.4, "{neighbour_difference}");
+    }
+}
@@ -1,36 +1,94 @@
 use crate::camera::Camera;
 use crate::consts::ASPECT_RATIO;
 use crate::geometry::aabb_box::AabbBox;
+use crate::geometry::bvh::BvhBuilder;
 use crate::geometry::hittable_world::HittableWorld;
+use crate::geometry::instance::Instance;
 use crate::geometry::moving_sphere::MovingSphere;
 use crate::geometry::sphere::Sphere;
 use crate::geometry::xy_rectangle::XyRectangle;
 use crate::geometry::xz_rectangle::XzRectangle;
 use crate::geometry::yz_rectangle::YzRectangle;
+use crate::light::Lights;
 use crate::material::Material;
 use crate::math::color::Color;
 use crate::math::perlin::Perlin;
+use crate::scene_description::{SceneDescription, SceneDescriptionError};
 use crate::texture::Texture;
-use glam::Vec3A;
+use glam::{Affine3A, Quat, Vec3, Vec3A};
 use rand::{Rng, SeedableRng};
 use rand_xoshiro::rand_core::RngCore;
+use std::path::Path;
+use std::sync::Arc;
 use tracy_full::zone;
 
+/// Names accepted by [`Scene::built_in`].
+pub const BUILT_IN_SCENES: [&str; 9] = [
+    "three_spheres",
+    "random",
+    "big_scene",
+    "two_spheres",
+    "two_perlin_spheres",
+    "perlin_and_earth",
+    "earth",
+    "simple_light",
+    "cornell_box",
+];
+
 pub struct Scene {
     hittable_list: HittableWorld,
+    lights: Lights,
     camera: Camera,
     background_color: Color,
 }
 
 impl Scene {
+    /// Creates a scene, the emissive primitives of `hittable_list` are
+    /// registered as its lights.
     pub fn new(hittable_list: HittableWorld, camera: Camera, background_color: Color) -> Self {
         Self {
+            lights: Lights::new(&hittable_list),
             hittable_list,
             camera,
             background_color,
         }
     }
 
+    /// Loads a scene from a TOML scene file, see [`SceneDescription`].
+    pub fn from_file(path: &Path) -> Result<Self, SceneDescriptionError> {
+        SceneDescription::load(path)?
+            .to_scene()
+            .map_err(|err| err.with_path(path))
+    }
+
+    /// Rebuilds the BVH of the objects of the scene, built-in and described
+    /// scenes are built with [`BvhBuilder::RandomMedian`].
+    pub fn build_bvh(&mut self, builder: BvhBuilder) {
+        self.hittable_list.build_bvh(builder);
+    }
+
+    pub fn to_description(&self) -> Result<SceneDescription, SceneDescriptionError> {
+        SceneDescription::from_scene(self)
+    }
+
+    /// Builds one of the [`BUILT_IN_SCENES`] by name.
+    pub fn built_in(name: &str, rng: &mut impl RngCore) -> Option<Self> {
+        let scene = match name {
+            "three_spheres" => Self::bench_three_spheres(),
+            "random" => Self::random(rng),
+            "big_scene" => Self::big_scene(),
+            "two_spheres" => Self::two_spheres(),
+            "two_perlin_spheres" => Self::two_perlin_spheres(rng),
+            "perlin_and_earth" => Self::perlin_and_earth(rng),
+            "earth" => Self::earth(),
+            "simple_light" => Self::simple_light(rng),
+            "cornell_box" => Self::cornell_box(),
+            _ => return None,
+        };
+
+        Some(scene)
+    }
+
     pub fn bench_three_spheres() -> Self {
         let mut world = HittableWorld::new();
 
@@ -46,19 +104,19 @@ impl Scene {
     }
 
     pub fn random(rng: &mut impl RngCore) -> Self {
-        Self {
-            hittable_list: random_hittable_list(rng),
-            camera: Camera::default(),
-            background_color: Color::new(0.70, 0.80, 1.00),
-        }
+        Self::new(
+            random_hittable_list(rng),
+            Camera::default(),
+            Color::new(0.70, 0.80, 1.00),
+        )
     }
 
     pub fn big_scene() -> Self {
-        Self {
-            hittable_list: fixed_big_scene(),
-            camera: Camera::default(),
-            background_color: Color::new(0.70, 0.80, 1.00),
-        }
+        Self::new(
+            fixed_big_scene(),
+            Camera::default(),
+            Color::new(0.70, 0.80, 1.00),
+        )
     }
 
     pub fn two_spheres() -> Self {
@@ -80,16 +138,16 @@ impl Scene {
         ));
         hittable_list.init_bvh_nodes();
 
-        Self {
+        Self::new(
             hittable_list,
-            camera: Camera::default(),
-            background_color: Color::new(0.70, 0.80, 1.00),
-        }
+            Camera::default(),
+            Color::new(0.70, 0.80, 1.00),
+        )
     }
 
     pub fn two_perlin_spheres(rng: &mut impl RngCore) -> Self {
-        let mut hittable_list = HittableWorld::new();
-        let perlin_texture = Texture::new_noise(Perlin::new(rng), 4.0);
+        let mut hittable_list = HittableWorld::with_seed(rng.next_u64());
+        let perlin_texture = Texture::new_noise(Perlin::from_seed(rng.next_u32()), 4.0);
 
         hittable_list.add_sphere(Sphere::new(
             Vec3A::new(0.0, -1000.0, 0.0),
@@ -103,16 +161,16 @@ impl Scene {
         ));
         hittable_list.init_bvh_nodes();
 
-        Self {
+        Self::new(
             hittable_list,
-            camera: Camera::default(),
-            background_color: Color::new(0.70, 0.80, 1.00),
-        }
+            Camera::default(),
+            Color::new(0.70, 0.80, 1.00),
+        )
     }
 
     pub fn perlin_and_earth(rng: &mut impl RngCore) -> Self {
-        let mut hittable_list = HittableWorld::new();
-        let perlin_texture = Texture::new_noise(Perlin::new(rng), 4.0);
+        let mut hittable_list = HittableWorld::with_seed(rng.next_u64());
+        let perlin_texture = Texture::new_noise(Perlin::from_seed(rng.next_u32()), 4.0);
         let earth_texture =
             Texture::new_image("earthmap.png".to_string()).expect("Failed to load earth texture");
         let earth_surface = Material::new_lambertian(earth_texture);
@@ -125,11 +183,11 @@ impl Scene {
         hittable_list.add_sphere(Sphere::new(Vec3A::new(0.0, 2.0, 0.0), 2.0, earth_surface));
         hittable_list.init_bvh_nodes();
 
-        Self {
+        Self::new(
             hittable_list,
-            camera: Camera::default(),
-            background_color: Color::new(0.70, 0.80, 1.00),
-        }
+            Camera::default(),
+            Color::new(0.70, 0.80, 1.00),
+        )
     }
 
     pub fn earth() -> Self {
@@ -141,17 +199,17 @@ impl Scene {
         hittable_list.add_sphere(globe);
         hittable_list.init_bvh_nodes();
 
-        Self {
+        Self::new(
             hittable_list,
-            camera: Camera::default(),
-            background_color: Color::new(0.70, 0.80, 1.00),
-        }
+            Camera::default(),
+            Color::new(0.70, 0.80, 1.00),
+        )
     }
 
     pub fn simple_light(rng: &mut impl RngCore) -> Self {
-        let mut hittable_list = HittableWorld::new();
+        let mut hittable_list = HittableWorld::with_seed(rng.next_u64());
 
-        let perlin_texture = Texture::new_noise(Perlin::new(rng), 4.0);
+        let perlin_texture = Texture::new_noise(Perlin::from_seed(rng.next_u32()), 4.0);
         let ground = Sphere::new(
             Vec3A::new(0.0, -1000.0, 0.0),
             1000.0,
@@ -181,11 +239,7 @@ impl Scene {
         );
         camera.set_time(0.0, 1.0);
 
-        Self {
-            hittable_list,
-            camera,
-            background_color: Color::black(),
-        }
+        Self::new(hittable_list, camera, Color::black())
     }
 
     pub fn cornell_box() -> Self {
@@ -232,15 +286,28 @@ impl Scene {
             555.0,
             555.0,
         ));
-        hittable_list.add_aabb_box(AabbBox::new(
-            Vec3A::new(130.0, 0.0, 65.0),
-            Vec3A::new(295.0, 165.0, 230.0),
-            white.clone(),
+
+        // Both blocks are the same unit cube, scaled and rotated.
+        let mut cube = HittableWorld::new();
+        cube.add_aabb_box(AabbBox::new(Vec3A::ZERO, Vec3A::ONE, white));
+        cube.init_bvh_nodes();
+        let cube = Arc::new(cube);
+
+        hittable_list.add_instance(Instance::new(
+            cube.clone(),
+            Affine3A::from_scale_rotation_translation(
+                Vec3::new(165.0, 165.0, 165.0),
+                Quat::from_rotation_y((-18.0f32).to_radians()),
+                Vec3::new(130.0, 0.0, 65.0),
+            ),
         ));
-        hittable_list.add_aabb_box(AabbBox::new(
-            Vec3A::new(265.0, 0.0, 295.0),
-            Vec3A::new(430.0, 330.0, 460.0),
-            white,
+        hittable_list.add_instance(Instance::new(
+            cube,
+            Affine3A::from_scale_rotation_translation(
+                Vec3::new(165.0, 330.0, 165.0),
+                Quat::from_rotation_y(15.0f32.to_radians()),
+                Vec3::new(265.0, 0.0, 295.0),
+            ),
         ));
 
         let mut camera = Camera::new(
@@ -270,7 +337,12 @@ impl Scene {
         &self.background_color
     }
 
+    pub fn lights(&self) -> &Lights {
+        &self.lights
+    }
+
     pub fn set_hittable_list(&mut self, hittable_list: HittableWorld) {
+        self.lights = Lights::new(&hittable_list);
         self.hittable_list = hittable_list;
     }
 
@@ -350,7 +422,7 @@ fn fixed_big_scene() -> HittableWorld {
 }
 
 fn random_hittable_list(rng: &mut impl RngCore) -> HittableWorld {
-    let mut world = HittableWorld::new();
+    let mut world = HittableWorld::with_seed(rng.next_u64());
 
     let material_ground = Material::new_lambertian_color(Color::new(0.5, 0.5, 0.5));
     world.add_sphere(Sphere::new(
@@ -0,0 +1,1066 @@
+use crate::camera::Camera;
+use crate::consts::ASPECT_RATIO;
+use crate::geometry::aabb_box::AabbBox;
+use crate::geometry::hittable_world::HittableWorld;
+use crate::geometry::instance::Instance;
+use crate::geometry::moving_sphere::MovingSphere;
+use crate::geometry::sphere::Sphere;
+use crate::geometry::triangle::Triangle;
+use crate::geometry::triangle_mesh::TriangleMesh;
+use crate::geometry::xy_rectangle::XyRectangle;
+use crate::geometry::xz_rectangle::XzRectangle;
+use crate::geometry::yz_rectangle::YzRectangle;
+use crate::import::obj::load_obj;
+use crate::material::Material;
+use crate::math::color::Color;
+use crate::math::perlin::Perlin;
+use crate::scene::Scene;
+use crate::texture::Texture;
+use glam::{Affine3A, EulerRot, Quat, Vec2, Vec3, Vec3A};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use toml::Spanned;
+use tracy_full::zone;
+
+/// Error produced while reading, building or writing a scene description.
+#[derive(Debug)]
+pub struct SceneDescriptionError {
+    path: Option<PathBuf>,
+    line: Option<usize>,
+    message: String,
+}
+
+impl SceneDescriptionError {
+    fn new(message: impl Into<String>) -> Self {
+        Self {
+            path: None,
+            line: None,
+            message: message.into(),
+        }
+    }
+
+    fn with_line(mut self, line: Option<usize>) -> Self {
+        self.line = line;
+        self
+    }
+
+    pub(crate) fn with_path(mut self, path: &Path) -> Self {
+        self.path = Some(path.to_path_buf());
+        self
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Line of the scene file the error refers to, starting at 1.
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for SceneDescriptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{}:", path.display())?;
+        }
+
+        if let Some(line) = self.line {
+            write!(f, "{line}:")?;
+        }
+
+        if self.path.is_some() || self.line.is_some() {
+            write!(f, " ")?;
+        }
+
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for SceneDescriptionError {}
+
+/// Declarative description of a [`Scene`], stored as TOML.
+///
+/// Textures and materials are named tables which are referenced by name from
+/// materials and objects. Image texture paths are relative to the working
+/// directory, like [`Texture::new_image`]. Groups are named lists of objects
+/// that are placed in the scene by `instance` objects, their geometry is
+/// shared by all of their instances.
+///
+/// ```toml
+/// background = [0.7, 0.8, 1.0]
+///
+/// [camera]
+/// look_from = [13.0, 2.0, 3.0]
+/// look_at = [0.0, 0.0, 0.0]
+/// vertical_fov = 20.0
+///
+/// [textures.orange]
+/// type = "solid_color"
+/// color = [0.8, 0.4, 0.1]
+///
+/// [materials.ground]
+/// type = "lambertian"
+/// albedo = "orange"
+///
+/// [[groups.ball]]
+/// type = "sphere"
+/// center = [0.0, 0.0, 0.0]
+/// radius = 1.0
+/// material = "ground"
+///
+/// [[objects]]
+/// type = "instance"
+/// group = "ball"
+/// scale = [1000.0, 1000.0, 1000.0]
+/// translate = [0.0, -1000.0, 0.0]
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneDescription {
+    pub background: [f32; 3],
+    pub camera: Spanned<CameraDescription>,
+    #[serde(default)]
+    pub textures: BTreeMap<String, Spanned<TextureDescription>>,
+    #[serde(default)]
+    pub materials: BTreeMap<String, Spanned<MaterialDescription>>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub groups: BTreeMap<String, Spanned<Vec<Spanned<ObjectDescription>>>>,
+    pub objects: Vec<Spanned<ObjectDescription>>,
+    #[serde(skip)]
+    line_starts: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraDescription {
+    pub look_from: [f32; 3],
+    pub look_at: [f32; 3],
+    #[serde(default = "default_vup")]
+    pub vup: [f32; 3],
+    pub vertical_fov: f32,
+    #[serde(default)]
+    pub aperture: f32,
+    #[serde(default = "default_focus_distance")]
+    pub focus_distance: f32,
+    #[serde(default = "default_shutter")]
+    pub shutter: [f32; 2],
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum TextureDescription {
+    SolidColor { color: [f32; 3] },
+    Checker { odd: String, even: String },
+    Noise { scale: f32, seed: u32 },
+    Image { path: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum MaterialDescription {
+    Lambertian { albedo: String },
+    Metal { albedo: [f32; 3], fuzz: f32 },
+    Dielectric { refraction_index: f32 },
+    DiffuseLight { emit: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ObjectDescription {
+    Sphere {
+        center: [f32; 3],
+        radius: f32,
+        material: String,
+    },
+    MovingSphere {
+        center0: [f32; 3],
+        center1: [f32; 3],
+        time0: f32,
+        time1: f32,
+        radius: f32,
+        material: String,
+    },
+    XyRectangle {
+        x0: f32,
+        x1: f32,
+        y0: f32,
+        y1: f32,
+        k: f32,
+        material: String,
+    },
+    XzRectangle {
+        x0: f32,
+        x1: f32,
+        z0: f32,
+        z1: f32,
+        k: f32,
+        material: String,
+    },
+    YzRectangle {
+        y0: f32,
+        y1: f32,
+        z0: f32,
+        z1: f32,
+        k: f32,
+        material: String,
+    },
+    AabbBox {
+        min: [f32; 3],
+        max: [f32; 3],
+        material: String,
+    },
+    Triangle {
+        vertices: [[f32; 3]; 3],
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        normals: Option<[[f32; 3]; 3]>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        uvs: Option<[[f32; 2]; 3]>,
+        material: String,
+    },
+    TriangleMesh {
+        positions: Vec<[f32; 3]>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        normals: Vec<[f32; 3]>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        uvs: Vec<[f32; 2]>,
+        indices: Vec<[u32; 3]>,
+        material: String,
+    },
+    /// Meshes of a Wavefront OBJ file, with the materials of its MTL files
+    /// unless `material` is given.
+    Obj {
+        path: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<String>,
+    },
+    /// Objects of a group, scaled, rotated by angles in degrees around the
+    /// X, Y then Z axes, and translated.
+    Instance {
+        group: String,
+        #[serde(default = "default_scale")]
+        scale: [f32; 3],
+        #[serde(default)]
+        rotate: [f32; 3],
+        #[serde(default)]
+        translate: [f32; 3],
+    },
+}
+
+fn default_vup() -> [f32; 3] {
+    [0.0, 1.0, 0.0]
+}
+
+fn default_focus_distance() -> f32 {
+    10.0
+}
+
+fn default_shutter() -> [f32; 2] {
+    [0.0, 1.0]
+}
+
+fn default_scale() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+impl SceneDescription {
+    /// Parses a TOML scene description.
+    ///
+    /// Only the syntax and the shape of the document are checked here, name
+    /// references are resolved by [`SceneDescription::to_scene`].
+    pub fn parse(source: &str) -> Result<Self, SceneDescriptionError> {
+        zone!();
+        let line_starts = line_starts(source);
+        let mut description: Self = toml::from_str(source).map_err(|err| {
+            let line = err.span().map(|span| line_of(&line_starts, span.start));
+            SceneDescriptionError::new(err.message()).with_line(line)
+        })?;
+        description.line_starts = line_starts;
+
+        Ok(description)
+    }
+
+    /// Reads and parses the scene description stored at `path`.
+    pub fn load(path: &Path) -> Result<Self, SceneDescriptionError> {
+        let source = std::fs::read_to_string(path).map_err(|err| {
+            SceneDescriptionError::new(format!("could not read scene file: {err}")).with_path(path)
+        })?;
+
+        Self::parse(&source).map_err(|err| err.with_path(path))
+    }
+
+    /// Builds the described scene, resolving texture and material names.
+    pub fn to_scene(&self) -> Result<Scene, SceneDescriptionError> {
+        self.to_scene_with_images(&BTreeMap::new())
+    }
+
+    /// Builds the described scene like [`SceneDescription::to_scene`], taking
+    /// the image textures found in `images` by path instead of loading them.
+    pub fn to_scene_with_images(
+        &self,
+        images: &BTreeMap<String, Texture>,
+    ) -> Result<Scene, SceneDescriptionError> {
+        zone!();
+        let mut builder = SceneBuilder {
+            description: self,
+            images,
+            textures: BTreeMap::new(),
+            materials: BTreeMap::new(),
+            groups: BTreeMap::new(),
+        };
+
+        let mut hittable_list = HittableWorld::new();
+        for object in self.objects.iter() {
+            builder.add_object(&mut hittable_list, object, &mut Vec::new())?;
+        }
+
+        if hittable_list.is_empty() {
+            return Err(SceneDescriptionError::new(
+                "the scene must contain at least one object",
+            ));
+        }
+        hittable_list.init_bvh_nodes();
+
+        let camera = builder.camera()?;
+        Ok(Scene::new(hittable_list, camera, to_color(self.background)))
+    }
+
+    /// Describes an existing scene, so that built-in scenes can be written to
+    /// scene files.
+    ///
+    /// Textures and materials that are equal are only described once.
+    pub fn from_scene(scene: &Scene) -> Result<Self, SceneDescriptionError> {
+        Ok(Self::from_scene_with_images(scene)?.0)
+    }
+
+    /// Describes an existing scene like [`SceneDescription::from_scene`],
+    /// also returning its image textures by path since the description only
+    /// has their paths.
+    pub fn from_scene_with_images(
+        scene: &Scene,
+    ) -> Result<(Self, BTreeMap<String, Texture>), SceneDescriptionError> {
+        zone!();
+        let mut exporter = SceneExporter::default();
+        let objects = exporter.objects(scene.hittable_list())?;
+
+        let camera = scene.camera();
+        let camera = CameraDescription {
+            look_from: camera.look_from().to_array(),
+            look_at: camera.look_at().to_array(),
+            vup: camera.vup().to_array(),
+            vertical_fov: camera.vertical_fov(),
+            aperture: camera.aperture(),
+            focus_distance: camera.focus_distance(),
+            shutter: [camera.time0(), camera.time1()],
+        };
+
+        let background = scene.background_color();
+        let description = Self {
+            background: [background.x, background.y, background.z],
+            camera: unspanned(camera),
+            textures: exporter.textures,
+            materials: exporter.materials,
+            groups: exporter.groups,
+            objects: objects.into_iter().map(unspanned).collect(),
+            line_starts: Vec::new(),
+        };
+
+        Ok((description, exporter.images))
+    }
+
+    pub fn to_toml_string(&self) -> Result<String, SceneDescriptionError> {
+        let mut value = toml::Value::try_from(self)
+            .map_err(|err| SceneDescriptionError::new(err.to_string()))?;
+        shorten_floats(&mut value);
+
+        toml::to_string(&value).map_err(|err| SceneDescriptionError::new(err.to_string()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), SceneDescriptionError> {
+        std::fs::write(path, self.to_toml_string()?).map_err(|err| {
+            SceneDescriptionError::new(format!("could not write scene file: {err}")).with_path(path)
+        })
+    }
+
+    fn error_at(&self, span: Range<usize>, message: impl Into<String>) -> SceneDescriptionError {
+        let line = if self.line_starts.is_empty() {
+            None
+        } else {
+            Some(line_of(&self.line_starts, span.start))
+        };
+
+        SceneDescriptionError::new(message).with_line(line)
+    }
+}
+
+struct SceneBuilder<'a> {
+    description: &'a SceneDescription,
+    /// Image textures by path, loaded from their file when missing.
+    images: &'a BTreeMap<String, Texture>,
+    textures: BTreeMap<&'a str, Texture>,
+    materials: BTreeMap<&'a str, Material>,
+    groups: BTreeMap<&'a str, Arc<HittableWorld>>,
+}
+
+impl<'a> SceneBuilder<'a> {
+    fn camera(&self) -> Result<Camera, SceneDescriptionError> {
+        let camera = &self.description.camera;
+        let [time0, time1] = camera.get_ref().shutter;
+        if time0 >= time1 {
+            return Err(self.description.error_at(
+                camera.span(),
+                "the camera shutter must close after it opens",
+            ));
+        }
+
+        let description = camera.get_ref();
+        let mut camera = Camera::new(
+            Vec3A::from_array(description.look_from),
+            Vec3A::from_array(description.look_at),
+            Vec3A::from_array(description.vup),
+            description.vertical_fov,
+            ASPECT_RATIO,
+            description.aperture,
+            description.focus_distance,
+        );
+        camera.set_time(time0, time1);
+
+        Ok(camera)
+    }
+
+    fn add_object(
+        &mut self,
+        hittable_list: &mut HittableWorld,
+        object: &'a Spanned<ObjectDescription>,
+        visiting_groups: &mut Vec<&'a str>,
+    ) -> Result<(), SceneDescriptionError> {
+        let span = object.span();
+        match object.get_ref() {
+            ObjectDescription::Sphere {
+                center,
+                radius,
+                material,
+            } => {
+                let material = self.material(material, span)?;
+                hittable_list.add_sphere(Sphere::new(
+                    Vec3A::from_array(*center),
+                    *radius,
+                    material,
+                ));
+            }
+            ObjectDescription::MovingSphere {
+                center0,
+                center1,
+                time0,
+                time1,
+                radius,
+                material,
+            } => {
+                if time0 >= time1 {
+                    return Err(self
+                        .description
+                        .error_at(span, "a moving sphere must have time0 before time1"));
+                }
+
+                let material = self.material(material, span)?;
+                hittable_list.add_moving_sphere(MovingSphere::new(
+                    Vec3A::from_array(*center0),
+                    Vec3A::from_array(*center1),
+                    *time0,
+                    *time1,
+                    *radius,
+                    material,
+                ));
+            }
+            ObjectDescription::XyRectangle {
+                x0,
+                x1,
+                y0,
+                y1,
+                k,
+                material,
+            } => {
+                let material = self.material(material, span)?;
+                hittable_list.add_xy_rectangle(XyRectangle::new(material, *x0, *x1, *y0, *y1, *k));
+            }
+            ObjectDescription::XzRectangle {
+                x0,
+                x1,
+                z0,
+                z1,
+                k,
+                material,
+            } => {
+                let material = self.material(material, span)?;
+                hittable_list.add_xz_rectangle(XzRectangle::new(material, *x0, *x1, *z0, *z1, *k));
+            }
+            ObjectDescription::YzRectangle {
+                y0,
+                y1,
+                z0,
+                z1,
+                k,
+                material,
+            } => {
+                let material = self.material(material, span)?;
+                hittable_list.add_yz_rectangle(YzRectangle::new(material, *y0, *y1, *z0, *z1, *k));
+            }
+            ObjectDescription::AabbBox { min, max, material } => {
+                let material = self.material(material, span)?;
+                hittable_list.add_aabb_box(AabbBox::new(
+                    Vec3A::from_array(*min),
+                    Vec3A::from_array(*max),
+                    material,
+                ));
+            }
+            ObjectDescription::Triangle {
+                vertices,
+                normals,
+                uvs,
+                material,
+            } => {
+                let material = self.material(material, span)?;
+                hittable_list.add_triangle(Triangle::new_with_attributes(
+                    vertices.map(Vec3A::from_array),
+                    normals.map(|normals| normals.map(Vec3A::from_array)),
+                    uvs.map(|uvs| uvs.map(Vec2::from_array)),
+                    material,
+                ));
+            }
+            ObjectDescription::TriangleMesh {
+                positions,
+                normals,
+                uvs,
+                indices,
+                material,
+            } => {
+                if !normals.is_empty() && normals.len() != positions.len() {
+                    return Err(self.description.error_at(
+                        span,
+                        "a triangle mesh needs one normal per position or none",
+                    ));
+                }
+
+                if !uvs.is_empty() && uvs.len() != positions.len() {
+                    return Err(self
+                        .description
+                        .error_at(span, "a triangle mesh needs one uv per position or none"));
+                }
+
+                if let Some(index) = indices
+                    .iter()
+                    .flatten()
+                    .find(|&&index| index as usize >= positions.len())
+                {
+                    return Err(self.description.error_at(
+                        span,
+                        format!("triangle mesh index {index} is out of the positions"),
+                    ));
+                }
+
+                let material = self.material(material, span)?;
+                hittable_list.add_triangle_mesh(TriangleMesh::new(
+                    positions.iter().copied().map(Vec3A::from_array).collect(),
+                    normals.iter().copied().map(Vec3A::from_array).collect(),
+                    uvs.iter().copied().map(Vec2::from_array).collect(),
+                    indices.clone(),
+                    material,
+                ));
+            }
+            ObjectDescription::Obj { path, material } => {
+                let material = match material {
+                    Some(material) => Some(self.material(material, span.clone())?),
+                    None => None,
+                };
+                let meshes = load_obj(Path::new(path)).map_err(|err| {
+                    self.description
+                        .error_at(span, format!("could not import `{path}`: {err}"))
+                })?;
+
+                for mut obj_mesh in meshes {
+                    if let Some(material) = &material {
+                        obj_mesh.mesh.set_material(material.clone());
+                    }
+                    hittable_list.add_triangle_mesh(obj_mesh.mesh);
+                }
+            }
+            ObjectDescription::Instance {
+                group,
+                scale,
+                rotate,
+                translate,
+            } => {
+                if scale.contains(&0.0) {
+                    return Err(self
+                        .description
+                        .error_at(span, "an instance can not have a scale of 0"));
+                }
+
+                let object = self.group(group, span, visiting_groups)?;
+                let [x, y, z] = rotate.map(f32::to_radians);
+                hittable_list.add_instance(Instance::new(
+                    object,
+                    Affine3A::from_scale_rotation_translation(
+                        Vec3::from_array(*scale),
+                        Quat::from_euler(EulerRot::ZYX, z, y, x),
+                        Vec3::from_array(*translate),
+                    ),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn group(
+        &mut self,
+        name: &'a str,
+        referenced_at: Range<usize>,
+        visiting: &mut Vec<&'a str>,
+    ) -> Result<Arc<HittableWorld>, SceneDescriptionError> {
+        if let Some(group) = self.groups.get(name) {
+            return Ok(group.clone());
+        }
+
+        let Some(description) = self.description.groups.get(name) else {
+            return Err(self
+                .description
+                .error_at(referenced_at, format!("unknown group `{name}`")));
+        };
+
+        let span = description.span();
+        if visiting.contains(&name) {
+            return Err(self
+                .description
+                .error_at(span, format!("group `{name}` instances itself")));
+        }
+
+        visiting.push(name);
+        let mut world = HittableWorld::new();
+        for object in description.get_ref() {
+            self.add_object(&mut world, object, visiting)?;
+        }
+        visiting.pop();
+
+        if world.is_empty() {
+            return Err(self.description.error_at(
+                span,
+                format!("group `{name}` must contain at least one object"),
+            ));
+        }
+        world.init_bvh_nodes();
+
+        let group = Arc::new(world);
+        self.groups.insert(name, group.clone());
+        Ok(group)
+    }
+
+    fn material(
+        &mut self,
+        name: &'a str,
+        referenced_at: Range<usize>,
+    ) -> Result<Material, SceneDescriptionError> {
+        if let Some(material) = self.materials.get(name) {
+            return Ok(material.clone());
+        }
+
+        let Some(description) = self.description.materials.get(name) else {
+            return Err(self
+                .description
+                .error_at(referenced_at, format!("unknown material `{name}`")));
+        };
+
+        let span = description.span();
+        let material = match description.get_ref() {
+            MaterialDescription::Lambertian { albedo } => {
+                Material::new_lambertian(self.texture(albedo, span, &mut Vec::new())?)
+            }
+            MaterialDescription::Metal { albedo, fuzz } => {
+                Material::new_metal(to_color(*albedo), *fuzz)
+            }
+            MaterialDescription::Dielectric { refraction_index } => {
+                Material::new_dielectric(*refraction_index)
+            }
+            MaterialDescription::DiffuseLight { emit } => {
+                Material::new_diffuse_light(self.texture(emit, span, &mut Vec::new())?)
+            }
+        };
+
+        self.materials.insert(name, material.clone());
+        Ok(material)
+    }
+
+    fn texture(
+        &mut self,
+        name: &'a str,
+        referenced_at: Range<usize>,
+        visiting: &mut Vec<&'a str>,
+    ) -> Result<Texture, SceneDescriptionError> {
+        if let Some(texture) = self.textures.get(name) {
+            return Ok(texture.clone());
+        }
+
+        let Some(description) = self.description.textures.get(name) else {
+            return Err(self
+                .description
+                .error_at(referenced_at, format!("unknown texture `{name}`")));
+        };
+
+        let span = description.span();
+        if visiting.contains(&name) {
+            return Err(self
+                .description
+                .error_at(span, format!("texture `{name}` references itself")));
+        }
+
+        visiting.push(name);
+        let texture = match description.get_ref() {
+            TextureDescription::SolidColor { color } => Texture::new_solid_color(to_color(*color)),
+            TextureDescription::Checker { odd, even } => Texture::new_checker(
+                self.texture(odd, span.clone(), visiting)?,
+                self.texture(even, span.clone(), visiting)?,
+            ),
+            TextureDescription::Noise { scale, seed } => {
+                Texture::new_noise(Perlin::from_seed(*seed), *scale)
+            }
+            TextureDescription::Image { path } => match self.images.get(path) {
+                Some(image) => image.clone(),
+                None => Texture::new_image(path.clone()).ok_or_else(|| {
+                    self.description
+                        .error_at(span, format!("could not load image texture `{path}`"))
+                })?,
+            },
+        };
+        visiting.pop();
+
+        self.textures.insert(name, texture.clone());
+        Ok(texture)
+    }
+}
+
+#[derive(Default)]
+struct SceneExporter {
+    textures: BTreeMap<String, Spanned<TextureDescription>>,
+    materials: BTreeMap<String, Spanned<MaterialDescription>>,
+    groups: BTreeMap<String, Spanned<Vec<Spanned<ObjectDescription>>>>,
+    /// Names of the groups already described, by the world they describe.
+    group_names: Vec<(Arc<HittableWorld>, String)>,
+    images: BTreeMap<String, Texture>,
+}
+
+impl SceneExporter {
+    fn objects(
+        &mut self,
+        world: &HittableWorld,
+    ) -> Result<Vec<ObjectDescription>, SceneDescriptionError> {
+        let mut objects = Vec::with_capacity(world.len());
+
+        for sphere in world.spheres() {
+            objects.push(ObjectDescription::Sphere {
+                center: sphere.center().to_array(),
+                radius: sphere.radius(),
+                material: self.material_name(sphere.material())?,
+            });
+        }
+
+        for sphere in world.moving_spheres() {
+            objects.push(ObjectDescription::MovingSphere {
+                center0: sphere.center0().to_array(),
+                center1: sphere.center1().to_array(),
+                time0: sphere.time0(),
+                time1: sphere.time1(),
+                radius: sphere.radius(),
+                material: self.material_name(sphere.material())?,
+            });
+        }
+
+        for rectangle in world.xy_rectangles() {
+            objects.push(ObjectDescription::XyRectangle {
+                x0: rectangle.x0(),
+                x1: rectangle.x1(),
+                y0: rectangle.y0(),
+                y1: rectangle.y1(),
+                k: rectangle.k(),
+                material: self.material_name(rectangle.material())?,
+            });
+        }
+
+        for rectangle in world.xz_rectangles() {
+            objects.push(ObjectDescription::XzRectangle {
+                x0: rectangle.x0(),
+                x1: rectangle.x1(),
+                z0: rectangle.z0(),
+                z1: rectangle.z1(),
+                k: rectangle.k(),
+                material: self.material_name(rectangle.material())?,
+            });
+        }
+
+        for rectangle in world.yz_rectangles() {
+            objects.push(ObjectDescription::YzRectangle {
+                y0: rectangle.y0(),
+                y1: rectangle.y1(),
+                z0: rectangle.z0(),
+                z1: rectangle.z1(),
+                k: rectangle.k(),
+                material: self.material_name(rectangle.material())?,
+            });
+        }
+
+        for aabb_box in world.aabb_boxes() {
+            objects.push(ObjectDescription::AabbBox {
+                min: aabb_box.box_min().to_array(),
+                max: aabb_box.box_max().to_array(),
+                material: self.material_name(aabb_box.material())?,
+            });
+        }
+
+        for triangle in world.triangles() {
+            objects.push(ObjectDescription::Triangle {
+                vertices: triangle.vertices().map(|vertex| vertex.to_array()),
+                normals: triangle
+                    .normals()
+                    .map(|normals| normals.map(|normal| normal.to_array())),
+                uvs: triangle.uvs().map(|uvs| uvs.map(|uv| uv.to_array())),
+                material: self.material_name(triangle.material())?,
+            });
+        }
+
+        for mesh in world.triangle_meshes() {
+            objects.push(ObjectDescription::TriangleMesh {
+                positions: mesh.positions().iter().map(Vec3A::to_array).collect(),
+                normals: mesh.normals().iter().map(Vec3A::to_array).collect(),
+                uvs: mesh.uvs().iter().map(Vec2::to_array).collect(),
+                indices: mesh.indices().to_vec(),
+                material: self.material_name(mesh.material())?,
+            });
+        }
+
+        for instance in world.instances() {
+            let (scale, rotation, translation) =
+                instance.transform().to_scale_rotation_translation();
+            let (z, y, x) = rotation.to_euler(EulerRot::ZYX);
+            objects.push(ObjectDescription::Instance {
+                group: self.group_name(instance.object())?,
+                scale: scale.to_array(),
+                rotate: [x, y, z].map(f32::to_degrees),
+                translate: translation.to_array(),
+            });
+        }
+
+        Ok(objects)
+    }
+
+    fn group_name(&mut self, world: &Arc<HittableWorld>) -> Result<String, SceneDescriptionError> {
+        if let Some((_, name)) = self
+            .group_names
+            .iter()
+            .find(|(described, _)| Arc::ptr_eq(described, world))
+        {
+            return Ok(name.clone());
+        }
+
+        let objects = self.objects(world)?;
+        let name = format!("group_{:03}", self.groups.len());
+        self.groups.insert(
+            name.clone(),
+            unspanned(objects.into_iter().map(unspanned).collect()),
+        );
+        self.group_names.push((world.clone(), name.clone()));
+
+        Ok(name)
+    }
+
+    fn material_name(&mut self, material: &Material) -> Result<String, SceneDescriptionError> {
+        let description = match material {
+            Material::Lambertian { albedo } => MaterialDescription::Lambertian {
+                albedo: self.texture_name(albedo)?,
+            },
+            Material::Metal { albedo, fuzz } => MaterialDescription::Metal {
+                albedo: [albedo.x, albedo.y, albedo.z],
+                fuzz: *fuzz,
+            },
+            Material::Dielectric { refraction_index } => MaterialDescription::Dielectric {
+                refraction_index: *refraction_index,
+            },
+            Material::DiffuseLight { emit } => MaterialDescription::DiffuseLight {
+                emit: self.texture_name(emit)?,
+            },
+        };
+
+        Ok(insert_named(&mut self.materials, "material", description))
+    }
+
+    fn texture_name(&mut self, texture: &Texture) -> Result<String, SceneDescriptionError> {
+        let description = match texture {
+            Texture::SolidColor(color) => TextureDescription::SolidColor {
+                color: [color.x, color.y, color.z],
+            },
+            Texture::Checker { odd, even } => TextureDescription::Checker {
+                odd: self.texture_name(odd)?,
+                even: self.texture_name(even)?,
+            },
+            Texture::Noise { noise, scale } => TextureDescription::Noise {
+                scale: *scale,
+                seed: noise.seed().ok_or_else(|| {
+                    SceneDescriptionError::new(
+                        "noise textures must be created with Perlin::from_seed to be described",
+                    )
+                })?,
+            },
+            Texture::Image { path, .. } => {
+                self.images
+                    .entry(path.clone())
+                    .or_insert_with(|| texture.clone());
+                TextureDescription::Image { path: path.clone() }
+            }
+        };
+
+        Ok(insert_named(&mut self.textures, "texture", description))
+    }
+}
+
+/// Returns the name of `description` in `entries`, adding it if no equal
+/// entry exists yet.
+fn insert_named<T: PartialEq>(
+    entries: &mut BTreeMap<String, Spanned<T>>,
+    prefix: &str,
+    description: T,
+) -> String {
+    if let Some((name, _)) = entries
+        .iter()
+        .find(|(_, entry)| *entry.get_ref() == description)
+    {
+        return name.clone();
+    }
+
+    let name = format!("{prefix}_{:03}", entries.len());
+    entries.insert(name.clone(), unspanned(description));
+
+    name
+}
+
+fn unspanned<T>(value: T) -> Spanned<T> {
+    Spanned::new(0..0, value)
+}
+
+fn to_color(value: [f32; 3]) -> Color {
+    Color::new(value[0], value[1], value[2])
+}
+
+/// Every float of a description is an `f32`, widening them to `f64` would
+/// write digits that do not exist in the scene, e.g. `0.7300000190734863`.
+fn shorten_floats(value: &mut toml::Value) {
+    match value {
+        toml::Value::Float(float) => {
+            *float = (*float as f32).to_string().parse().unwrap_or(*float);
+        }
+        toml::Value::Array(array) => array.iter_mut().for_each(shorten_floats),
+        toml::Value::Table(table) => table
+            .iter_mut()
+            .for_each(|(_, value)| shorten_floats(value)),
+        _ => {}
+    }
+}
+
+fn line_starts(source: &str) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(source.match_indices('\n').map(|(index, _)| index + 1))
+        .collect()
+}
+
+fn line_of(line_starts: &[usize], offset: usize) -> usize {
+    line_starts.partition_point(|&start| start <= offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scene::Scene;
+    use crate::scene_description::SceneDescription;
+
+    #[test]
+    fn built_in_scene_round_trips() {
+        let description = SceneDescription::from_scene(&Scene::cornell_box()).unwrap();
+        let source = description.to_toml_string().unwrap();
+
+        let parsed = SceneDescription::parse(&source).unwrap();
+        let scene = parsed.to_scene().unwrap();
+
+        assert_eq!(scene.hittable_list().len(), 8);
+        assert_eq!(parsed.to_toml_string().unwrap(), source);
+    }
+
+    #[test]
+    fn triangle_mesh_index_is_checked() {
+        let source = r#"
+background = [0.0, 0.0, 0.0]
+
+[camera]
+look_from = [0.0, 0.0, -10.0]
+look_at = [0.0, 0.0, 0.0]
+vertical_fov = 40.0
+
+[materials.glass]
+type = "dielectric"
+refraction_index = 1.5
+
+[[objects]]
+type = "triangle_mesh"
+positions = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+indices = [[0, 1, 3]]
+material = "glass"
+"#;
+
+        let error = SceneDescription::parse(source)
+            .unwrap()
+            .to_scene()
+            .err()
+            .unwrap();
+
+        assert_eq!(error.line(), Some(13));
+    }
+
+    #[test]
+    fn unknown_material_reports_line() {
+        let source = r#"
+background = [0.0, 0.0, 0.0]
+
+[camera]
+look_from = [0.0, 0.0, -10.0]
+look_at = [0.0, 0.0, 0.0]
+vertical_fov = 40.0
+
+[[objects]]
+type = "sphere"
+center = [0.0, 0.0, 0.0]
+radius = 1.0
+material = "missing"
+"#;
+
+        let error = SceneDescription::parse(source)
+            .unwrap()
+            .to_scene()
+            .err()
+            .unwrap();
+
+        assert_eq!(error.line(), Some(9));
+        assert!(error.message().contains("missing"));
+    }
+
+    #[test]
+    fn syntax_error_reports_line() {
+        let source = "background = [0.0, 0.0, 0.0]\n\n[camera]\nvertical_fov = = 40.0\n";
+
+        let error = SceneDescription::parse(source).err().unwrap();
+
+        assert_eq!(error.line(), Some(4));
+    }
+}
@@ -18,6 +18,7 @@ pub enum Texture {
         scale: f32,
     },
     Image {
+        path: String,
         data: Vec<u8>,
         width: usize,
         height: usize,
@@ -46,7 +47,7 @@ impl Texture {
 
     pub fn new_image(filename: String) -> Option<Self> {
         zone!();
-        let file = File::open(filename);
+        let file = File::open(&filename);
         if let Err(err) = file {
             eprintln!("Could not open texture image : {err}");
             return None;
@@ -71,6 +72,7 @@ impl Texture {
         let bytes = buffer[..info.buffer_size()].to_vec();
 
         Some(Texture::Image {
+            path: filename,
             data: bytes,
             width: info.width as usize,
             height: info.height as usize,
@@ -78,6 +80,18 @@ impl Texture {
         })
     }
 
+    /// Creates an image texture from 8 bits RGB pixels, row by row from the
+    /// top of the image. `path` only names the image.
+    pub fn new_image_data(path: String, data: Vec<u8>, width: usize, height: usize) -> Self {
+        Texture::Image {
+            path,
+            data,
+            width,
+            height,
+            bytes_per_scanline: BYTES_PER_PIXEL * width,
+        }
+    }
+
     pub fn value(&self, u: f32, v: f32, p: Vec3A) -> Color {
         zone!();
         match self {
@@ -100,6 +114,7 @@ impl Texture {
                 width,
                 height,
                 bytes_per_scanline,
+                ..
             } => get_texture_image_value(u, v, data, *width, *height, *bytes_per_scanline),
         }
     }
@@ -0,0 +1,139 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// Order in which the tiles of an image are handed to the render threads.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+pub enum TileOrder {
+    /// Row by row from the top left corner.
+    Scanline,
+    /// Outwards from the center of the image.
+    Spiral,
+    /// Along a Hilbert curve, which keeps consecutive tiles next to each other.
+    Hilbert,
+}
+
+/// Rectangle of pixels rendered together, from its top left corner.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Tile {
+    pub fn pixel_count(&self) -> usize {
+        self.width * self.height
+    }
+
+    /// Gets the position in the image of the pixel at `index`, row by row in
+    /// the tile.
+    pub fn pixel_position(&self, index: usize) -> (usize, usize) {
+        (self.x + index % self.width, self.y + index / self.width)
+    }
+}
+
+/// Splits an image in tiles of `tile_size` pixels, smaller on the right and
+/// bottom edges, sorted in the given order.
+///
+/// # Panics
+///
+/// If `tile_size` is zero.
+pub fn tiles(width: usize, height: usize, tile_size: usize, order: TileOrder) -> Vec<Tile> {
+    assert!(tile_size > 0, "Tiles should have pixels");
+    let columns = width.div_ceil(tile_size);
+    let rows = height.div_ceil(tile_size);
+
+    let mut grid: Vec<(usize, usize)> = (0..rows)
+        .flat_map(|row| (0..columns).map(move |column| (column, row)))
+        .collect();
+    match order {
+        TileOrder::Scanline => {}
+        TileOrder::Spiral => {
+            let center = ((columns as f32 - 1.0) / 2.0, (rows as f32 - 1.0) / 2.0);
+            grid.sort_by(|a, b| spiral_key(*a, center).total_cmp(&spiral_key(*b, center)));
+        }
+        TileOrder::Hilbert => {
+            let side = columns.max(rows).next_power_of_two();
+            grid.sort_by_key(|&(column, row)| hilbert_index(side, column, row));
+        }
+    }
+
+    grid.into_iter()
+        .map(|(column, row)| {
+            let (x, y) = (column * tile_size, row * tile_size);
+            Tile {
+                x,
+                y,
+                width: tile_size.min(width - x),
+                height: tile_size.min(height - y),
+            }
+        })
+        .collect()
+}
+
+/// Sorts the tiles by square ring around the center, then by angle.
+fn spiral_key((column, row): (usize, usize), center: (f32, f32)) -> f32 {
+    let dx = column as f32 - center.0;
+    let dy = row as f32 - center.1;
+    let ring = dx.abs().max(dy.abs()).ceil();
+    let angle = dy.atan2(dx) + std::f32::consts::PI;
+
+    // The angles are below 2π < 8, so the rings stay ordered.
+    ring * 8.0 + angle
+}
+
+/// Gets the distance along the Hilbert curve filling a `side` by `side` grid,
+/// `side` being a power of two, of the cell at `x`, `y`.
+fn hilbert_index(side: usize, mut x: usize, mut y: usize) -> usize {
+    let mut index = 0;
+    let mut scale = side / 2;
+    while scale > 0 {
+        let rx = usize::from(x & scale > 0);
+        let ry = usize::from(y & scale > 0);
+        index += scale * scale * ((3 * rx) ^ ry);
+
+        // Rotates the quadrant so that the curve continues in it.
+        if ry == 0 {
+            if rx == 1 {
+                x = side - 1 - x;
+                y = side - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        scale /= 2;
+    }
+
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tile::{tiles, TileOrder};
+
+    #[test]
+    fn tiles_cover_each_pixel_once() {
+        for order in [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert] {
+            let tiles = tiles(50, 37, 16, order);
+            let mut covered = vec![0; 50 * 37];
+            for tile in &tiles {
+                for index in 0..tile.pixel_count() {
+                    let (x, y) = tile.pixel_position(index);
+                    covered[y * 50 + x] += 1;
+                }
+            }
+
+            assert_eq!(tiles.len(), 4 * 3, "{order:?}");
+            assert!(covered.iter().all(|&count| count == 1), "{order:?}");
+        }
+
+        // Consecutive tiles of the Hilbert curve are neighbours.
+        let hilbert = tiles(64, 64, 16, TileOrder::Hilbert);
+        for pair in hilbert.windows(2) {
+            let distance = pair[0].x.abs_diff(pair[1].x) + pair[0].y.abs_diff(pair[1].y);
+            assert_eq!(distance, 16);
+        }
+        let spiral = tiles(48, 48, 16, TileOrder::Spiral);
+        assert_eq!((spiral[0].x, spiral[0].y), (16, 16));
+    }
+}
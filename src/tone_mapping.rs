@@ -0,0 +1,156 @@
+use crate::math::color::Color;
+use glam::{Mat3A, Vec3A};
+
+/// Operator compressing linear radiance to the displayable range.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ToneMapping {
+    /// Clamps each channel to one.
+    Clamp,
+    /// Reinhard on the luminance, which tends to white only at infinity.
+    Reinhard,
+    /// Reinhard on the luminance, reaching white at the luminance `white`.
+    ExtendedReinhard { white: f32 },
+    /// Fit of the ACES reference rendering and sRGB output transforms.
+    Aces,
+    /// AgX base look, which desaturates the highlights towards white.
+    Agx,
+}
+
+impl ToneMapping {
+    /// Maps a linear color to linear display values between zero and one.
+    pub fn apply(&self, color: Color) -> Color {
+        let color = Color::new(color.x.max(0.0), color.y.max(0.0), color.z.max(0.0));
+
+        let mapped = match self {
+            ToneMapping::Clamp => color,
+            ToneMapping::Reinhard => scale_luminance(color, |l| l / (1.0 + l)),
+            ToneMapping::ExtendedReinhard { white } => {
+                let white_squared = white * white;
+                scale_luminance(color, |l| l * (1.0 + l / white_squared) / (1.0 + l))
+            }
+            ToneMapping::Aces => aces(color),
+            ToneMapping::Agx => agx(color),
+        };
+
+        mapped.clamp(0.0, 1.0)
+    }
+}
+
+/// sRGB opto-electronic transfer function, from linear to encoded values.
+pub fn srgb_oetf(value: f32) -> f32 {
+    if value <= 0.003_130_8 {
+        12.92 * value
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn scale_luminance(color: Color, map: impl Fn(f32) -> f32) -> Color {
+    let luminance = color.luminance();
+    if luminance <= 0.0 {
+        return Color::black();
+    }
+
+    color * (map(luminance) / luminance)
+}
+
+fn to_vec(color: Color) -> Vec3A {
+    Vec3A::new(color.x, color.y, color.z)
+}
+
+fn to_color(vector: Vec3A) -> Color {
+    Color::new(vector.x, vector.y, vector.z)
+}
+
+/// Stephen Hill's fit, with the sRGB to ACES AP1 conversion and back, the
+/// matrices are given by columns.
+fn aces(color: Color) -> Color {
+    let input = Mat3A::from_cols(
+        Vec3A::new(0.59719, 0.07600, 0.02840),
+        Vec3A::new(0.35458, 0.90834, 0.13383),
+        Vec3A::new(0.04823, 0.01566, 0.83777),
+    );
+    let output = Mat3A::from_cols(
+        Vec3A::new(1.60475, -0.10208, -0.00327),
+        Vec3A::new(-0.53108, 1.10813, -0.07276),
+        Vec3A::new(-0.07367, -0.00605, 1.07602),
+    );
+
+    let v = input * to_vec(color);
+    let fitted =
+        (v * (v + 0.024_578_6) - 0.000_090_537) / (v * (0.983_729 * v + 0.432_951) + 0.238_081);
+
+    to_color(output * fitted)
+}
+
+/// Minimal AgX, with the polynomial fit of its default contrast curve.
+fn agx(color: Color) -> Color {
+    const MIN_EV: f32 = -12.473_93;
+    const MAX_EV: f32 = 4.026_069;
+    let inset = Mat3A::from_cols(
+        Vec3A::new(0.842_479, 0.042_328_2, 0.042_375_7),
+        Vec3A::new(0.078_433_6, 0.878_469, 0.078_433_6),
+        Vec3A::new(0.079_223_7, 0.079_166_1, 0.879_143),
+    );
+    let outset = Mat3A::from_cols(
+        Vec3A::new(1.196_879, -0.052_896_9, -0.052_971_6),
+        Vec3A::new(-0.098_020_9, 1.151_903, -0.098_043_5),
+        Vec3A::new(-0.099_029_7, -0.098_961_2, 1.151_074),
+    );
+
+    let v = (inset * to_vec(color)).max(Vec3A::splat(1e-10));
+    let x = ((Vec3A::new(v.x.log2(), v.y.log2(), v.z.log2()) - MIN_EV) / (MAX_EV - MIN_EV))
+        .clamp(Vec3A::ZERO, Vec3A::ONE);
+
+    let x2 = x * x;
+    let x4 = x2 * x2;
+    let curve =
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+            - 0.002_32;
+
+    // The curve produces display encoded values, decoded back to linear.
+    let linear = (outset * curve).max(Vec3A::ZERO);
+    to_color(Vec3A::new(
+        linear.x.powf(2.2),
+        linear.y.powf(2.2),
+        linear.z.powf(2.2),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::math::color::Color;
+    use crate::tone_mapping::{srgb_oetf, ToneMapping};
+
+    #[test]
+    fn srgb_oetf_is_piecewise() {
+        assert_eq!(srgb_oetf(0.0), 0.0);
+        assert!((srgb_oetf(0.002) - 0.02584).abs() < 1e-5);
+        assert!((srgb_oetf(0.5) - 0.735_357).abs() < 1e-5);
+        assert!((srgb_oetf(1.0) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn tone_mappings_keep_highlights_in_range() {
+        let operators = [
+            ToneMapping::Clamp,
+            ToneMapping::Reinhard,
+            ToneMapping::ExtendedReinhard { white: 15.0 },
+            ToneMapping::Aces,
+            ToneMapping::Agx,
+        ];
+
+        for operator in operators {
+            let black = operator.apply(Color::black());
+            let grey = operator.apply(Color::new(0.18, 0.18, 0.18));
+            let light = operator.apply(Color::new(15.0, 15.0, 15.0));
+
+            assert!(black.x < 0.01, "{operator:?}");
+            assert!(grey.x > black.x && light.x > grey.x, "{operator:?}");
+            assert!(light.x <= 1.0 && light.x > 0.8, "{operator:?}");
+        }
+        let white =
+            ToneMapping::ExtendedReinhard { white: 15.0 }.apply(Color::new(15.0, 15.0, 15.0));
+        assert!((white.x - 1.0).abs() < 1e-5);
+    }
+}